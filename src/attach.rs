@@ -0,0 +1,126 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const IMAGE_EXTENSIONS: &'static [&'static str] = &["png", "jpg", "jpeg", "gif", "svg", "webp"];
+
+/// A file given to `--attach`
+#[derive(Debug)]
+pub struct Attachment {
+    pub name:    String,
+    pub content: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn read(path: &str) -> Result<Attachment, GliError> {
+        let mut content = vec![];
+        File::open(path)
+            .map_err(|_| GliError::NotFound(format!("file {}", path)))?
+            .read_to_end(&mut content)?;
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_owned());
+        Ok(Attachment { name, content })
+    }
+
+    pub fn is_image(&self) -> bool {
+        let extension = self.name.rsplit('.').next().unwrap_or("").to_lowercase();
+        self.name.contains('.') && IMAGE_EXTENSIONS.contains(&extension.as_str())
+    }
+
+    /// The content of logs and other non-empty text files
+    pub fn text(&self) -> Option<&str> {
+        match str::from_utf8(&self.content) {
+            Ok(text) if !text.is_empty() && !self.is_image() => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Images are embedded, other files linked
+    pub fn link(&self, url: &str) -> String {
+        format!("{}[{}]({})", if self.is_image() { "!" } else { "" }, self.name, url)
+    }
+}
+
+/// Absolute path of a file to attach, so queued issues still find it when synced from another directory
+pub fn resolve(path: &str) -> Result<String, GliError> {
+    let absolute = fs::canonicalize(env::current_dir()?.join(path)).map_err(|_| GliError::NotFound(format!("file {}", path)))?;
+    if !absolute.is_file() {
+        return Err(GliError::Other(format!("{} is not a file", path)));
+    }
+    Ok(absolute.to_string_lossy().into_owned())
+}
+
+/// `multipart/form-data` body with the attachment as its only field, returns the content type and the body
+pub fn multipart(field: &str, attachment: &Attachment) -> (String, Vec<u8>) {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos()))
+        .unwrap_or(0);
+    let boundary = format!("gli-{:x}", nanos);
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        boundary,
+        field,
+        attachment.name.replace('"', "_")
+    ).into_bytes();
+    body.extend_from_slice(&attachment.content);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+/// Uploads the files and appends links to them to the text
+pub fn with_attachments(http: &mut HttpClient, project: &Project, text: Option<String>, paths: &[String]) -> Result<Option<String>, GliError> {
+    if paths.is_empty() {
+        return Ok(text);
+    }
+    let mut links = vec![];
+    for path in paths {
+        let attachment = Attachment::read(path)?;
+        links.push(forge::upload_attachment(http, project, &attachment)?);
+    }
+    let links = links.join("\n");
+    Ok(Some(match text {
+        Some(ref text) if !text.trim().is_empty() => format!("{}\n\n{}", text, links),
+        _ => links,
+    }))
+}
+
+#[cfg(test)]
+mod attach_tests {
+    use super::*;
+
+    fn attachment(name: &str, content: &str) -> Attachment {
+        Attachment {
+            name:    name.to_owned(),
+            content: content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn linking_attachments() {
+        assert_eq!(attachment("crash.PNG", "").link("https://x/crash.PNG"), "![crash.PNG](https://x/crash.PNG)");
+        assert_eq!(attachment("server.log", "boom").link("https://x/1"), "[server.log](https://x/1)");
+        assert_eq!(attachment("server.log", "boom").text(), Some("boom"));
+        assert_eq!(attachment("png", "").is_image(), false);
+    }
+
+    #[test]
+    fn building_multipart_bodies() {
+        let (content_type, body) = multipart("file", &attachment("a\"b.log", "boom"));
+        let boundary = &content_type["multipart/form-data; boundary=".len()..];
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+        assert!(body.contains("name=\"file\"; filename=\"a_b.log\""));
+        assert!(body.ends_with(&format!("\r\n\r\nboom\r\n--{}--\r\n", boundary)));
+    }
+}
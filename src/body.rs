@@ -0,0 +1,89 @@
+use errors::GliError;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+const TRUNCATION_NOTICE: &'static str = "[… truncated by gli …]\n";
+
+/// Wraps piped content (compiler or test output usually) in a code block,
+/// with a fence longer than any run of backticks it contains
+pub fn fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in content.chars() {
+        current = if c == '`' { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}\n{}\n{}", fence, content.trim_right_matches('\n'), fence)
+}
+
+/// Keeps the end of the content (where logs say what went wrong) so it fits in `max` characters,
+/// starting at a line boundary. `None` when it already fits.
+pub fn truncate(content: &str, max: usize) -> Option<String> {
+    let length = content.chars().count();
+    if length <= max {
+        return None;
+    }
+    let keep = max.saturating_sub(TRUNCATION_NOTICE.chars().count());
+    let start = content
+        .char_indices()
+        .nth(length - keep)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len());
+    let start = match content[start..].find('\n') {
+        Some(i) if start + i + 1 < content.len() => start + i + 1,
+        _ => start,
+    };
+    Some(format!("{}{}", TRUNCATION_NOTICE, &content[start..]))
+}
+
+/// Text of a new issue: the positional argument, the content of `--body-file`, or stdin when either is `-`.
+/// Piped content is fenced, and cut to `max` characters with a warning.
+pub fn read(text: Option<String>, body_file: Option<&str>, max: usize) -> Result<Option<String>, GliError> {
+    if text.is_some() && body_file.is_some() {
+        return Err(GliError::Other("Give either the text or --body-file, not both".to_owned()));
+    }
+    let piped = text.as_ref().map(|t| t == "-").unwrap_or(false) || body_file == Some("-");
+    let mut content = String::new();
+    if piped {
+        io::stdin().read_to_string(&mut content)?;
+    } else if let Some(path) = body_file {
+        File::open(path)
+            .map_err(|_| GliError::NotFound(format!("file {}", path)))?
+            .read_to_string(&mut content)?;
+    } else {
+        return Ok(text);
+    }
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    // Room for the fence
+    let max = if piped { max.saturating_sub(20) } else { max };
+    if let Some(truncated) = truncate(&content, max) {
+        eprintln!(
+            "gli: the text is longer than the {} characters the forge accepts, only its end is kept",
+            max
+        );
+        content = truncated;
+    }
+    Ok(Some(if piped { fence(&content) } else { content }))
+}
+
+#[cfg(test)]
+mod body_tests {
+    use super::*;
+
+    #[test]
+    fn fencing() {
+        assert_eq!(fence("error: boom\n"), "```\nerror: boom\n```");
+        assert_eq!(fence("see ```rust```"), "````\nsee ```rust```\n````");
+    }
+
+    #[test]
+    fn truncating() {
+        assert_eq!(truncate("short", 10), None);
+        let log = format!("{}failures: 1\n", "running\n".repeat(10));
+        assert_eq!(truncate(&log, 40), Some(format!("{}failures: 1\n", TRUNCATION_NOTICE)));
+    }
+}
@@ -0,0 +1,151 @@
+use cache;
+use config::*;
+use errors::GliError;
+use forge;
+use git;
+use http::HttpClient;
+use regex;
+use regex::Regex;
+
+const MAX_SLUG_LENGTH: usize = 50;
+
+/// `Crash on startup (config)` becomes `crash-on-startup-config`
+pub fn slug(title: &str) -> String {
+    let words: Vec<String> = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_owned())
+        .collect();
+    let mut slug = String::new();
+    for word in words {
+        if !slug.is_empty() && slug.chars().count() + 1 + word.chars().count() > MAX_SLUG_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug
+}
+
+/// Fills `{number}` and `{slug}` in the pattern
+pub fn branch_name(pattern: &str, issue: &Issue) -> String {
+    pattern
+        .replace("{number}", &issue.number.to_string())
+        .replace("{slug}", &slug(&issue.title))
+}
+
+/// Anchored regex for the branch names of `pattern`, `{number}` being the only group
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let mut expression = "^".to_owned();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        expression.push_str(&regex::escape(&rest[..start]));
+        expression.push_str(if &rest[start..end + 1] == "{number}" { r"(\d+)" } else { ".+?" });
+        rest = &rest[end + 1..];
+    }
+    expression.push_str(&regex::escape(rest));
+    expression.push('$');
+    Regex::new(&expression).ok()
+}
+
+/// Issue number of a branch named after `pattern`, `None` unless the whole branch name matches it
+pub fn issue_from_branch(pattern: &str, branch: &str) -> Option<u64> {
+    if !pattern.contains("{number}") {
+        return None;
+    }
+    pattern_regex(pattern)
+        .and_then(|r| r.captures(branch))
+        .and_then(|c| c.get(1))
+        .and_then(|m| m.as_str().parse().ok())
+}
+
+/// The issue the checked out branch was started for, if any
+pub fn current_issue(config: &Config) -> Option<u64> {
+    git::current_branch()
+        .ok()
+        .and_then(|branch| issue_from_branch(config.branch_pattern(), &branch))
+}
+
+/// Whether `branch` is the one `gli start` creates for `issue`. The number alone isn't enough,
+/// `2024-q3-release` matches `{number}-{slug}` but has nothing to do with issue #2024.
+pub fn is_issue_branch(pattern: &str, branch: &str, issue: &Issue) -> bool {
+    branch_name(pattern, issue) == branch
+}
+
+/// Like `current_issue`, but only if the issue exists and the branch is named after its title.
+/// The local cache is looked at before the API.
+pub fn verified_issue(http: &mut HttpClient, project: &Project, config: &Config) -> Result<Option<u64>, GliError> {
+    let branch = match git::current_branch() {
+        Ok(branch) => branch,
+        Err(_) => return Ok(None),
+    };
+    let number = match issue_from_branch(config.branch_pattern(), &branch) {
+        Some(number) => number,
+        None => return Ok(None),
+    };
+    let cached = cache::load(project)
+        .ok()
+        .and_then(|c| c.issues.into_iter().find(|i| i.number == number));
+    if let Some(issue) = cached {
+        if is_issue_branch(config.branch_pattern(), &branch, &issue) {
+            return Ok(Some(number));
+        }
+    }
+    match forge::get_issue(http, project, number) {
+        Ok(ref issue) if is_issue_branch(config.branch_pattern(), &branch, issue) => Ok(Some(number)),
+        Ok(_) | Err(GliError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod branch_tests {
+    use super::*;
+
+    #[test]
+    fn naming_branches() {
+        assert_eq!(slug("Crash on startup (config)"), "crash-on-startup-config");
+        assert_eq!(slug("  "), "");
+        assert!(slug(&"word ".repeat(20)).len() <= MAX_SLUG_LENGTH);
+    }
+
+    #[test]
+    fn finding_issues_in_branches() {
+        assert_eq!(issue_from_branch("{number}-{slug}", "12-crash-on-startup"), Some(12));
+        assert_eq!(issue_from_branch("feature/{number}-{slug}", "feature/7-tui"), Some(7));
+        assert_eq!(issue_from_branch("feature/{number}-{slug}", "master"), None);
+        assert_eq!(issue_from_branch("{slug}-{number}", "crash-on-startup-12"), Some(12));
+        assert_eq!(issue_from_branch("{number}-{slug}", "fix-2-bugs"), None);
+        assert_eq!(issue_from_branch("{slug}-{number}", "fix-2-bugs-12"), Some(12));
+        assert_eq!(issue_from_branch("{slug}-{number}", "fix-2-bugs"), None);
+        assert_eq!(issue_from_branch("feature/{number}-{slug}", "2024-q3-release"), None);
+        assert_eq!(issue_from_branch("{number}-{slug}", "2024"), None);
+    }
+
+    #[test]
+    fn verifying_issue_branches() {
+        let issue = Issue {
+            number:       2024,
+            title:        "Crash on startup".to_owned(),
+            body:         None,
+            state:        "open".to_owned(),
+            labels:       vec![],
+            author:       None,
+            assignees:    vec![],
+            created_at:   "2017-11-02T10:33:44Z".to_owned(),
+            updated_at:   "2017-11-02T10:33:44Z".to_owned(),
+            url:          "https://gitlab.example.org/a/b/issues/2024".to_owned(),
+            milestone:    None,
+            confidential: false,
+        };
+        assert!(is_issue_branch("{number}-{slug}", "2024-crash-on-startup", &issue));
+        assert!(!is_issue_branch("{number}-{slug}", "2024-q3-release", &issue));
+    }
+}
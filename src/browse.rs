@@ -0,0 +1,163 @@
+use config::*;
+use errors::GliError;
+use git;
+use std::env;
+use std::fs;
+
+/// What `gli b` can open
+#[derive(Debug, PartialEq)]
+pub enum Page {
+    Project,
+    Issue(u64),
+    MergeRequest(u64),
+    Branch(String),
+    Commit(String),
+    /// Path relative to the repository root, `None` for the root itself
+    File {
+        path:      Option<String>,
+        reference: String,
+        is_dir:    bool,
+        lines:     Option<(u64, Option<u64>)>,
+    },
+}
+
+pub fn project_url(project: &Project) -> String {
+    match &project.place {
+        &Place::Gitlab(_) => format!("https://{}/{}", project.account.domain, project.name()),
+        &Place::Github => format!("https://github.com/{}", project.name()),
+    }
+}
+
+pub fn page_url(project: &Project, page: &Page) -> String {
+    let root = project_url(project);
+    let gitlab = match &project.place {
+        &Place::Gitlab(_) => true,
+        &Place::Github => false,
+    };
+    match page {
+        &Page::Project => root,
+        &Page::Issue(n) => format!("{}/issues/{}", root, n),
+        &Page::MergeRequest(n) if gitlab => format!("{}/merge_requests/{}", root, n),
+        &Page::MergeRequest(n) => format!("{}/pull/{}", root, n),
+        &Page::Branch(ref b) => format!("{}/tree/{}", root, b),
+        &Page::Commit(ref sha) => format!("{}/commit/{}", root, sha),
+        &Page::File {
+            ref path,
+            ref reference,
+            is_dir,
+            lines,
+        } => {
+            let mut url = format!(
+                "{}/{}/{}",
+                root,
+                if is_dir { "tree" } else { "blob" },
+                reference
+            );
+            if let &Some(ref path) = path {
+                url.push('/');
+                url.push_str(path);
+            }
+            match lines {
+                Some((start, Some(end))) if gitlab => url.push_str(&format!("#L{}-{}", start, end)),
+                Some((start, Some(end))) => url.push_str(&format!("#L{}-L{}", start, end)),
+                Some((start, None)) => url.push_str(&format!("#L{}", start)),
+                None => {}
+            }
+            url
+        }
+    }
+}
+
+/// Splits `src/main.rs:42-50` into the path and the line range
+pub fn parse_location(location: &str) -> Result<(String, Option<(u64, Option<u64>)>), GliError> {
+    let mut parts = location.rsplitn(2, ':');
+    let (lines, path) = match (parts.next(), parts.next()) {
+        (Some(lines), Some(path)) => (lines, path),
+        _ => return Ok((location.to_owned(), None)),
+    };
+    let invalid = || GliError::Other(format!("Invalid line range `{}`, expected eg `42` or `42-50`", lines));
+    let mut bounds = lines.splitn(2, '-');
+    let start = bounds
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(&invalid)?;
+    let end = match bounds.next() {
+        Some(e) => Some(e.parse().map_err(|_| invalid())?),
+        None => None,
+    };
+    Ok((path.to_owned(), Some((start, end))))
+}
+
+/// Page of a file or directory of the working copy, at the current branch (or commit when detached)
+pub fn file_page(location: &str) -> Result<Page, GliError> {
+    let (path, lines) = parse_location(location)?;
+    let repo = git::open_repository()?;
+    let workdir = repo.workdir()
+        .ok_or_else(|| GliError::Other("This is a bare repository".to_owned()))?;
+    let absolute = fs::canonicalize(env::current_dir()?.join(&path))
+        .map_err(|_| GliError::NotFound(format!("file {}", path)))?;
+    let relative = absolute
+        .strip_prefix(&fs::canonicalize(workdir)?)
+        .map_err(|_| GliError::Other(format!("{} is outside of the repository", path)))?
+        .to_string_lossy()
+        .replace('\\', "/");
+    let reference = match git::current_branch() {
+        Ok(branch) => branch,
+        Err(_) => git::commit_sha(None)?,
+    };
+    Ok(Page::File {
+        path:      if relative.is_empty() { None } else { Some(relative) },
+        reference: reference,
+        is_dir:    absolute.is_dir(),
+        lines:     lines,
+    })
+}
+
+#[cfg(test)]
+mod url_tests {
+    use super::*;
+
+    fn project(place: Place) -> Project {
+        Project {
+            place:   place,
+            owner:   "clevercloud".to_owned(),
+            repo:    "issues-helper".to_owned(),
+            account: Account::new(Forge::Gitlab, "gitlab.example.org", "token"),
+        }
+    }
+
+    #[test]
+    fn building_urls() {
+        let gitlab = project(Place::Gitlab("gitlab.example.org".to_owned()));
+        let github = project(Place::Github);
+        let file = Page::File {
+            path:      Some("src/main.rs".to_owned()),
+            reference: "master".to_owned(),
+            is_dir:    false,
+            lines:     Some((42, Some(50))),
+        };
+        assert_eq!(
+            page_url(&gitlab, &file),
+            "https://gitlab.example.org/clevercloud/issues-helper/blob/master/src/main.rs#L42-50"
+        );
+        assert_eq!(
+            page_url(&github, &file),
+            "https://github.com/clevercloud/issues-helper/blob/master/src/main.rs#L42-L50"
+        );
+        assert_eq!(
+            page_url(&github, &Page::MergeRequest(3)),
+            "https://github.com/clevercloud/issues-helper/pull/3"
+        );
+    }
+
+    #[test]
+    fn parsing_locations() {
+        assert_eq!(parse_location("src/main.rs").unwrap(), ("src/main.rs".to_owned(), None));
+        assert_eq!(parse_location("src/main.rs:42").unwrap(), ("src/main.rs".to_owned(), Some((42, None))));
+        assert_eq!(
+            parse_location("src/main.rs:42-50").unwrap(),
+            ("src/main.rs".to_owned(), Some((42, Some(50))))
+        );
+        assert!(parse_location("src/main.rs:abc").is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use serde_json;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use xdg::BaseDirectories;
+
+/// Incremental fetches don't see deleted or transferred issues, every issue is downloaded again after that long
+const FULL_REFRESH_SECS: u64 = 7 * 24 * 3600;
+
+/// Issues of a project, stored in `$XDG_CACHE_HOME/issues-helper/<domain>/<owner>/<repo>.json`
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct IssueCache {
+    /// Most recent `updated_at` of the cached issues, used to only fetch what changed since
+    pub last_updated:  Option<String>,
+    /// When the last successful fetch happened, in seconds since the epoch
+    #[serde(default)]
+    pub fetched_at:    Option<u64>,
+    /// When every issue was last downloaded, in seconds since the epoch
+    #[serde(default)]
+    pub full_fetch_at: Option<u64>,
+    pub issues:        Vec<Issue>,
+}
+
+fn cache_path(project: &Project) -> Result<PathBuf, GliError> {
+    Ok(BaseDirectories::with_prefix("issues-helper")?.place_cache_file(format!(
+        "{}/{}/{}.json",
+        project.account.domain, project.owner, project.repo
+    ))?)
+}
+
+pub fn load(project: &Project) -> Result<IssueCache, GliError> {
+    let path = cache_path(project)?;
+    if !path.exists() {
+        return Ok(IssueCache::default());
+    }
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Cached issues, or an error telling to run `gli fetch` if there are none
+pub fn load_issues(project: &Project) -> Result<Vec<Issue>, GliError> {
+    let cache = load(project)?;
+    if cache.fetched_at.is_none() && cache.last_updated.is_none() {
+        return Err(GliError::NotFound(format!(
+            "no cached issues for {}, run `gli fetch` first",
+            project.name()
+        )));
+    }
+    Ok(cache.issues)
+}
+
+fn save(project: &Project, cache: &IssueCache) -> Result<(), GliError> {
+    let mut f = File::create(cache_path(project)?)?;
+    f.write_all(serde_json::to_string(cache)?.as_bytes())?;
+    Ok(())
+}
+
+/// Adds issues found outside of `gli fetch`, replacing the cached ones with the same number
+pub fn add_issues(project: &Project, issues: Vec<Issue>) -> Result<(), GliError> {
+    let mut cache = load(project)?;
+    for issue in issues {
+        cache.issues.retain(|i| i.number != issue.number);
+        cache.issues.push(issue);
+    }
+    cache.issues.sort_by(|a, b| b.number.cmp(&a.number));
+    save(project, &cache)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Fetches the issues updated since the last fetch and merges them in the cache.
+/// Every issue is downloaded again with `full` or once a week, dropping the deleted and transferred ones.
+pub fn fetch(http: &mut HttpClient, project: &Project, full: bool) -> Result<String, GliError> {
+    let mut cache = load(project)?;
+    let started = now();
+    let full = full || cache
+        .full_fetch_at
+        .map(|t| started.saturating_sub(t) > FULL_REFRESH_SECS)
+        .unwrap_or(true);
+    let since = if full { None } else { cache.last_updated.clone() };
+    let updated = forge::fetch_issues(http, project, None, since.as_ref().map(|d| d.as_str()))?;
+    let count = updated.len();
+    if full {
+        cache.issues.clear();
+        cache.last_updated = None;
+        cache.full_fetch_at = Some(started);
+    }
+    for issue in updated {
+        if cache
+            .last_updated
+            .as_ref()
+            .map(|d| &issue.updated_at > d)
+            .unwrap_or(true)
+        {
+            cache.last_updated = Some(issue.updated_at.clone());
+        }
+        cache.issues.retain(|i| i.number != issue.number);
+        cache.issues.push(issue);
+    }
+    cache.issues.sort_by(|a, b| b.number.cmp(&a.number));
+    cache.fetched_at = Some(started);
+    save(project, &cache)?;
+    Ok(format!(
+        "Fetched {} {}issue(s), {} issues cached for {}",
+        count,
+        if full { "" } else { "updated " },
+        cache.issues.len(),
+        project.name()
+    ))
+}
+
+/// Issues whose title, body or labels contain every word of the query, case insensitive
+pub fn search<'a>(issues: &'a [Issue], query: &str) -> Vec<&'a Issue> {
+    let terms: Vec<String> = query.split_whitespace().map(|t| t.to_lowercase()).collect();
+    issues
+        .iter()
+        .filter(|i| {
+            let text = format!(
+                "{} {} {}",
+                i.title,
+                i.body.clone().unwrap_or_default(),
+                i.labels.join(" ")
+            ).to_lowercase();
+            terms.iter().all(|t| text.contains(t.as_str()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+
+    fn issue(number: u64, title: &str, body: &str, labels: Vec<&str>) -> Issue {
+        Issue {
+            number:       number,
+            title:        title.to_owned(),
+            body:         Some(body.to_owned()),
+            state:        "open".to_owned(),
+            labels:       labels.into_iter().map(|l| l.to_owned()).collect(),
+            author:       None,
+            assignees:    vec![],
+            created_at:   "2017-11-02T10:33:44Z".to_owned(),
+            updated_at:   "2017-11-02T10:33:44Z".to_owned(),
+            url:          format!("https://gitlab.example.org/a/b/issues/{}", number),
+            milestone:    None,
+            confidential: false,
+        }
+    }
+
+    #[test]
+    fn searching_every_term() {
+        let issues = vec![
+            issue(1, "Crash on startup", "panics when config is missing", vec!["bug"]),
+            issue(2, "Add a TUI", "browse issues from the terminal", vec!["feature"]),
+        ];
+        let found: Vec<u64> = search(&issues, "CONFIG crash").iter().map(|i| i.number).collect();
+        assert_eq!(found, vec![1]);
+        let found: Vec<u64> = search(&issues, "feature terminal").iter().map(|i| i.number).collect();
+        assert_eq!(found, vec![2]);
+        assert!(search(&issues, "crash terminal").is_empty());
+    }
+}
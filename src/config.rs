@@ -1,21 +1,198 @@
+use errors::GliError;
 use git2;
 use nom::IResult::Done;
 use nom::be_u8;
 use rprompt::prompt_reply_stdout;
 use std;
-use std::error::Error;
 use std::fmt;
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 use std::str::FromStr;
 use toml;
+use toml::Value;
 use xdg::BaseDirectories;
 
-#[derive(Deserialize, Serialize)]
+/// Version of the configuration file format written by this release.
+/// Files without a `version` key are version 1 (single gitlab account).
+pub const CONFIG_VERSION: i64 = 3;
+
+pub const DEFAULT_BRANCH_PATTERN: &'static str = "{number}-{slug}";
+pub const DEFAULT_IN_PROGRESS_LABEL: &'static str = "in progress";
+
+/// Optional settings come before `accounts`, toml can't have values after tables
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
-    pub gitlab_domain: String,
-    pub gitlab_token:  String,
-    pub github_token:  String,
+    pub version:           i64,
+    /// Name of the branches created by `gli start`, `{number}` and `{slug}` are replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_pattern:    Option<String>,
+    /// Label added by `gli start --in-progress`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_progress_label: Option<String>,
+    /// What the commit-msg hook does with commits which don't reference an issue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_policy:     Option<CommitPolicy>,
+    /// Commands whose output `gli o --with-context` adds to the issue, eg `rustc -V`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_commands:  Option<Vec<String>>,
+    pub accounts:          Vec<Account>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitPolicy {
+    /// Accept them
+    Allow,
+    /// Accept them with a warning
+    Warn,
+    /// Refuse them
+    Require,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    Gitlab,
+    Github,
+}
+
+impl fmt::Display for Forge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Forge::Gitlab => write!(f, "gitlab"),
+            &Forge::Github => write!(f, "github"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    pub forge:                      Forge,
+    pub domain:                     String,
+    pub token:                      String,
+    /// Overrides the `https_proxy` environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https_proxy:                Option<String>,
+    /// Overrides the `no_proxy` environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy:                   Option<String>,
+    /// PEM file with additional CA certificates (or the server's self-signed certificate)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_file:                    Option<String>,
+    /// PKCS#12 (`.p12`) file used to authenticate with a client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert:                Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert_password:       Option<String>,
+    /// Accepts certificates issued for another name. The certificate must still be trusted,
+    /// add it with `ca_file` if it's self-signed.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub skip_hostname_verification: bool,
+    /// Github only: attachments which can't go to a gist are committed to the `gli-attachments`
+    /// branch of the project. Off by default, it needs push access and publishes the files on public repositories.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub commit_attachments:         bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl Account {
+    pub fn new(forge: Forge, domain: &str, token: &str) -> Account {
+        Account {
+            forge:                      forge,
+            domain:                     domain.to_owned(),
+            token:                      token.to_owned(),
+            https_proxy:                None,
+            no_proxy:                   None,
+            ca_file:                    None,
+            client_cert:                None,
+            client_cert_password:       None,
+            skip_hostname_verification: false,
+            commit_attachments:         false,
+        }
+    }
+}
+
+impl Config {
+    pub fn branch_pattern(&self) -> &str {
+        self.branch_pattern
+            .as_ref()
+            .map(|p| p.as_str())
+            .unwrap_or(DEFAULT_BRANCH_PATTERN)
+    }
+
+    pub fn in_progress_label(&self) -> &str {
+        self.in_progress_label
+            .as_ref()
+            .map(|l| l.as_str())
+            .unwrap_or(DEFAULT_IN_PROGRESS_LABEL)
+    }
+
+    pub fn commit_policy(&self) -> CommitPolicy {
+        self.commit_policy.clone().unwrap_or(CommitPolicy::Warn)
+    }
+
+    pub fn context_commands(&self) -> &[String] {
+        self.context_commands
+            .as_ref()
+            .map(|c| c.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn account_for_domain(&self, domain: &str) -> Option<&Account> {
+        self.accounts.iter().find(|a| a.domain == domain)
+    }
+
+    /// Returns a list of human readable problems, empty if the config is usable
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.version != CONFIG_VERSION {
+            problems.push(format!(
+                "version is {}, expected {}",
+                self.version, CONFIG_VERSION
+            ));
+        }
+        if self.accounts.is_empty() {
+            problems.push("no account is configured".to_owned());
+        }
+        if !self.branch_pattern().contains("{number}") {
+            problems.push("branch_pattern must contain {number}".to_owned());
+        }
+        for (i, a) in self.accounts.iter().enumerate() {
+            if a.domain.is_empty() {
+                problems.push(format!("accounts.{}.domain is empty", i));
+            }
+            if a.token.is_empty() {
+                problems.push(format!("accounts.{}.token is empty", i));
+            }
+            if a.forge == Forge::Github && a.domain != "github.com" {
+                problems.push(format!(
+                    "accounts.{}.domain is {}, only github.com is supported for github accounts",
+                    i, a.domain
+                ));
+            }
+            for (key, file) in vec![("ca_file", &a.ca_file), ("client_cert", &a.client_cert)] {
+                if let &Some(ref path) = file {
+                    if !Path::new(path).is_file() {
+                        problems.push(format!("accounts.{}.{}: {} does not exist", i, key, path));
+                    }
+                }
+            }
+            if self.accounts[..i].iter().any(|b| b.domain == a.domain) {
+                problems.push(format!(
+                    "accounts.{}.domain: {} is configured more than once",
+                    i, a.domain
+                ));
+            }
+        }
+        problems
+    }
 }
 
 #[derive(Debug)]
@@ -26,9 +203,10 @@ pub enum Place {
 
 #[derive(Debug)]
 pub struct Project {
-    pub place: Place,
-    pub owner: String,
-    pub repo:  String,
+    pub place:   Place,
+    pub owner:   String,
+    pub repo:    String,
+    pub account: Account,
 }
 
 impl Project {
@@ -37,7 +215,7 @@ impl Project {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum IssueFilter {
     Open,
     Closed,
@@ -64,7 +242,231 @@ impl fmt::Display for IssueFilter {
     }
 }
 
-fn parse_origin(origin: &str) -> Result<(String, String, String), Box<Error>> {
+#[derive(Clone, Debug, PartialEq)]
+pub enum MergeRequestFilter {
+    Open,
+    Closed,
+    Merged,
+    All,
+}
+
+impl FromStr for MergeRequestFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "open" => Ok(MergeRequestFilter::Open),
+            "closed" => Ok(MergeRequestFilter::Closed),
+            "merged" => Ok(MergeRequestFilter::Merged),
+            "all" => Ok(MergeRequestFilter::All),
+            _ => Err(format!("Unknown state: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for MergeRequestFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &MergeRequestFilter::Open => write!(f, "open"),
+            &MergeRequestFilter::Closed => write!(f, "closed"),
+            &MergeRequestFilter::Merged => write!(f, "merged"),
+            &MergeRequestFilter::All => write!(f, "all"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+/// Everything needed to create an issue, whatever the forge.
+/// `confidential`, `due_date`, `weight`, `epic_id` and `issue_type` are only supported by gitlab,
+/// `milestone_id` is the milestone number on github.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NewIssue {
+    pub title:        String,
+    pub text:         Option<String>,
+    pub labels:       Vec<String>,
+    pub assignee:     Option<String>,
+    pub confidential: bool,
+    pub due_date:     Option<String>,
+    pub weight:       Option<u64>,
+    pub milestone_id: Option<u64>,
+    pub epic_id:      Option<u64>,
+    pub issue_type:   Option<String>,
+    /// Absolute paths of files to upload and link at the end of the description
+    pub attachments:  Vec<String>,
+}
+
+/// A merge request (pull request on github) to open from `source_branch` into `target_branch`
+#[derive(Clone, Debug, Default)]
+pub struct NewMergeRequest {
+    pub source_branch: String,
+    pub target_branch: String,
+    pub title:         String,
+    pub body:          Option<String>,
+    pub labels:        Vec<String>,
+    pub assignee:      Option<String>,
+    pub reviewers:     Vec<String>,
+    pub draft:         bool,
+}
+
+/// An existing issue, as returned by either forge.
+/// `state` is always `open` or `closed`, dates are ISO 8601 strings.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Issue {
+    pub number:       u64,
+    pub title:        String,
+    pub body:         Option<String>,
+    pub state:        String,
+    pub labels:       Vec<String>,
+    pub author:       Option<String>,
+    pub assignees:    Vec<String>,
+    pub created_at:   String,
+    pub updated_at:   String,
+    pub url:          String,
+    /// Title of the milestone
+    #[serde(default)]
+    pub milestone:    Option<String>,
+    /// Only visible to project members, gitlab only
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+impl Issue {
+    pub fn matches(&self, filter: &IssueFilter) -> bool {
+        self.state == filter.to_string()
+    }
+
+    /// Header, description and comments, as printed by `gli show`
+    pub fn details(&self, comments: &[Comment]) -> String {
+        let mut text = format!(
+            "#{} {}\n{} by {} on {}\nlabels: {}\nassignees: {}\n{}\n\n{}\n",
+            self.number,
+            self.title,
+            self.state,
+            self.author.clone().unwrap_or("?".to_owned()),
+            short_date(&self.created_at),
+            self.labels.join(", "),
+            self.assignees.join(", "),
+            self.url,
+            self.body.clone().unwrap_or_default()
+        );
+        for c in comments {
+            text.push_str(&format!(
+                "\n--- {} on {}\n{}\n",
+                c.author.clone().unwrap_or("?".to_owned()),
+                short_date(&c.created_at),
+                c.body
+            ));
+        }
+        text
+    }
+
+    /// One line description, as printed by `gli l`
+    pub fn summary(&self) -> String {
+        format!(
+            "#{} {} {} {} {}",
+            self.number,
+            self.state,
+            self.title,
+            short_date(&self.created_at),
+            self.url
+        )
+    }
+}
+
+/// An existing merge request (pull request on github).
+/// `state` is `open`, `closed` or `merged`, `sha` is the head commit of the source branch.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MergeRequest {
+    pub number:        u64,
+    pub title:         String,
+    pub body:          Option<String>,
+    pub state:         String,
+    pub author:        Option<String>,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub sha:           String,
+    pub draft:         bool,
+    pub reviewers:     Vec<String>,
+    pub created_at:    String,
+    pub updated_at:    String,
+    pub url:           String,
+}
+
+/// What needs to happen before a merge request can be merged
+#[derive(Clone, Debug, Default)]
+pub struct MergeRequestStatus {
+    /// Name and status of each pipeline, check run or commit status
+    pub checks:         Vec<(String, String)>,
+    pub approved_by:    Vec<String>,
+    /// Only reported by gitlab
+    pub approvals_left: Option<u64>,
+}
+
+/// A comment on an issue, system notes excluded
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Comment {
+    pub author:     Option<String>,
+    pub body:       String,
+    pub created_at: String,
+}
+
+/// A label of a project, `color` is written `#rrggbb`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Label {
+    pub name:        String,
+    pub color:       String,
+    pub description: Option<String>,
+}
+
+/// Changes to a label, `None` fields are left as they are
+#[derive(Clone, Debug, Default)]
+pub struct LabelUpdate {
+    pub new_name:    Option<String>,
+    pub color:       Option<String>,
+    pub description: Option<String>,
+}
+
+/// Something that happened to an issue: `closed`, `reopened`, `added label bug`, …
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IssueEvent {
+    pub actor:      Option<String>,
+    pub action:     String,
+    pub created_at: String,
+}
+
+/// What `gli search` looks for: free text plus optional qualifiers
+#[derive(Debug, Default)]
+pub struct IssueQuery {
+    pub text:   String,
+    pub state:  Option<IssueFilter>,
+    pub labels: Vec<String>,
+    pub author: Option<String>,
+}
+
+impl IssueQuery {
+    /// Checks the qualifiers (not the text) against an issue, used when searching the local cache
+    pub fn matches(&self, issue: &Issue) -> bool {
+        self.state.as_ref().map(|s| issue.matches(s)).unwrap_or(true) && self.labels.iter().all(|l| issue.labels.contains(l))
+            && self.author
+                .as_ref()
+                .map(|a| issue.author.as_ref() == Some(a))
+                .unwrap_or(true)
+    }
+}
+
+/// `2017-11-02T10:33:44.123Z` -> `2017-11-02 10:33`
+pub fn short_date(date: &str) -> String {
+    date.replacen('T', " ", 1).chars().take(16).collect()
+}
+
+fn parse_origin(origin: &str) -> Result<(String, String, String), GliError> {
     named!(
         raw_ssh,
         do_parse!(tag!("git@") >> domain: take_while!(|c: u8| c as char != ':') >> tag!(":") >> (domain))
@@ -106,7 +508,7 @@ fn parse_origin(origin: &str) -> Result<(String, String, String), Box<Error>> {
 
     match address(origin.as_bytes()) {
         Done(_, (domain, owner, repo)) => Ok((domain, owner, repo)),
-        e => Err(format!("Couldn't parse 'orgin' remote: {:?}", e).into()),
+        e => Err(GliError::RemoteUnparseable(format!("{:?}", e))),
     }
 }
 
@@ -114,6 +516,27 @@ fn parse_origin(origin: &str) -> Result<(String, String, String), Box<Error>> {
 mod parsing_tests {
     use super::*;
 
+    #[test]
+    fn masking_secrets() {
+        assert_eq!(mask("glpat-xxxxxxxxxxxxab12"), "****ab12");
+        assert_eq!(mask("hunter2"), "****");
+        assert_eq!(mask("abcd"), "****");
+    }
+
+    #[test]
+    fn parsing_project_specs() {
+        assert_eq!(
+            parse_project_spec("gitlab:group/sub/project").unwrap(),
+            ("gitlab".into(), "group/sub".into(), "project".into())
+        );
+        assert_eq!(
+            parse_project_spec("github:CleverCloud/issues-helper").unwrap(),
+            ("github".into(), "CleverCloud".into(), "issues-helper".into())
+        );
+        assert!(parse_project_spec("CleverCloud/issues-helper").is_err());
+        assert!(parse_project_spec("github:issues-helper").is_err());
+    }
+
     #[test]
     fn parsing_raw_ssh_with_ext() {
         let raw_ssh_with_ext = "git@github.com:CleverCloud/issues-helper.git";
@@ -188,39 +611,80 @@ mod parsing_tests {
     }
 }
 
-pub fn extract_project(config: &Config) -> Result<Project, Box<Error>> {
-    let repo = git2::Repository::open(".")?;
-    let remote = repo.find_remote("origin")?;
-    let origin = remote.url().ok_or("origin is not valid UTF8")?;
+pub fn extract_project(config: &Config) -> Result<Project, GliError> {
+    let repo = git2::Repository::open(".")
+        .map_err(|e| GliError::Other(format!("Run gli from the root of a git repository ({})", e.message())))?;
+    let remote = repo.find_remote("origin")
+        .map_err(|_| GliError::RemoteUnparseable("the repository has no `origin` remote".to_owned()))?;
+    let origin = remote
+        .url()
+        .ok_or_else(|| GliError::RemoteUnparseable("origin is not valid UTF8".to_owned()))?;
     let (domain, owner, repo) = parse_origin(&origin)?;
+    project_for(config, domain, owner, repo)
+}
 
-    if domain == config.gitlab_domain {
-        Ok(Project {
-            place: Place::Gitlab(domain),
-            owner,
-            repo,
-        })
-    } else if domain == "github.com" {
-        Ok(Project {
-            place: Place::Github,
+/// Builds a project hosted on `domain`, with the matching account from the config
+pub fn project_for(config: &Config, domain: String, owner: String, repo: String) -> Result<Project, GliError> {
+    match config.account_for_domain(&domain) {
+        Some(account) => Ok(Project {
+            place: match account.forge {
+                Forge::Gitlab => Place::Gitlab(domain.clone()),
+                Forge::Github => Place::Github,
+            },
             owner,
             repo,
-        })
-    } else {
-        Err(format!(
-            "Couldn't find credentials for {}, only {} is supported",
-            domain, config.gitlab_domain
-        ).into())
+            account: account.clone(),
+        }),
+        None => Err(GliError::UnknownHost(domain)),
+    }
+}
+
+/// Splits `github:owner/repo`, `gitlab:group/subgroup/project` or `gitlab.example.org:group/project`
+/// into the forge (or domain), the owner and the repository
+pub fn parse_project_spec(spec: &str) -> Result<(String, String, String), GliError> {
+    let invalid = || {
+        GliError::Other(format!(
+            "Invalid project `{}`, expected eg github:owner/repo or gitlab:group/project",
+            spec
+        ))
+    };
+    let mut parts = spec.splitn(2, ':');
+    let (host, path) = match (parts.next(), parts.next()) {
+        (Some(host), Some(path)) if !host.is_empty() => (host, path.trim_matches('/')),
+        _ => return Err(invalid()),
+    };
+    match path.rfind('/') {
+        Some(i) if i > 0 && i + 1 < path.len() => Ok((host.to_owned(), path[..i].to_owned(), path[i + 1..].to_owned())),
+        _ => Err(invalid()),
     }
 }
 
-pub fn init_config() -> Result<(), Box<Error>> {
+/// A project given on the command line (see `parse_project_spec`), `gitlab` stands for the first gitlab account
+pub fn project_from_spec(config: &Config, spec: &str) -> Result<Project, GliError> {
+    let (host, owner, repo) = parse_project_spec(spec)?;
+    let domain = match host.as_str() {
+        "github" => "github.com".to_owned(),
+        "gitlab" => config
+            .accounts
+            .iter()
+            .find(|a| match a.forge {
+                Forge::Gitlab => true,
+                Forge::Github => false,
+            })
+            .map(|a| a.domain.clone())
+            .ok_or_else(|| GliError::Config("No gitlab account is configured".to_owned()))?,
+        _ => host.clone(),
+    };
+    project_for(config, domain, owner, repo)
+}
+
+pub fn init_config() -> Result<(), GliError> {
     let config = ask_config()?;
     save_config(&config)?;
     Ok(())
 }
 
-pub fn ask_config() -> Result<Config, Box<Error>> {
+pub fn ask_config() -> Result<Config, GliError> {
     println!("Hi! First I need to know the domain name of your gitlab instance (eg gitlab.example.org)");
     let gitlab_domain = prompt_reply_stdout("Gitlab domain name: ")?;
     println!("Thanks, now I need a personal access token to authenticate calls.");
@@ -235,31 +699,369 @@ pub fn ask_config() -> Result<Config, Box<Error>> {
     let github_token = prompt_reply_stdout("Github personal access token: ")?;
 
     Ok(Config {
-        gitlab_domain: gitlab_domain.to_owned(),
-        gitlab_token:  gitlab_token.to_owned(),
-        github_token:  github_token.to_owned(),
+        version:           CONFIG_VERSION,
+        branch_pattern:    None,
+        in_progress_label: None,
+        commit_policy:     None,
+        context_commands:  None,
+        accounts:          vec![
+            Account::new(Forge::Gitlab, &gitlab_domain, &gitlab_token),
+            Account::new(Forge::Github, "github.com", &github_token),
+        ],
     })
 }
 
-pub fn save_config(config: &Config) -> Result<(), Box<Error>> {
+pub fn save_config(config: &Config) -> Result<(), GliError> {
     let toml = toml::to_string(&config)?;
-    let path = BaseDirectories::new()?.place_config_file("issues-helper")?;
+    write_config_file("issues-helper", &toml)
+}
+
+fn write_config_file(name: &str, contents: &str) -> Result<(), GliError> {
+    let path = BaseDirectories::new()?.place_config_file(name)?;
     let mut f = File::create(path)?;
-    f.write(toml.as_bytes())?;
+    f.write_all(contents.as_bytes())?;
 
     Ok(())
 }
 
-pub fn read_config() -> Result<Config, Box<Error>> {
+pub fn read_config() -> Result<Config, GliError> {
+    let value = read_config_value()?;
+    let config: Config = value.try_into()?;
+    Ok(config)
+}
+
+/// Reads the raw configuration file, upgrading it to `CONFIG_VERSION` if needed.
+/// The original file is kept next to the new one as `issues-helper.v<N>.bak`.
+fn read_config_value() -> Result<Value, GliError> {
     let path = BaseDirectories::new()?.place_config_file("issues-helper")?;
-    let missing_config: Box<Error> = format!(
-        r#"It looks like you've not configured me yet.
-Please run `gli init` so we can get going!"#
-    ).into();
-    let mut f = File::open(path).map_err(|_| missing_config)?;
+    let mut f = File::open(&path).map_err(|_| GliError::ConfigMissing)?;
 
     let mut contents = String::new();
     f.read_to_string(&mut contents)?;
-    let config: Config = toml::from_str(&contents)?;
-    Ok(config)
+    let value: Value = toml::from_str(&contents)?;
+    let version = config_version(&value)?;
+    if version == CONFIG_VERSION {
+        return Ok(value);
+    }
+
+    let migrated = migrate_config(value)?;
+    let backup = format!("issues-helper.v{}.bak", version);
+    fs::copy(&path, BaseDirectories::new()?.place_config_file(&backup)?)?;
+    write_config_file("issues-helper", &toml::to_string(&migrated)?)?;
+    eprintln!(
+        "Your configuration has been upgraded to version {}, the previous one has been saved as `{}`",
+        CONFIG_VERSION, backup
+    );
+    Ok(migrated)
+}
+
+fn config_version(value: &Value) -> Result<i64, GliError> {
+    match value.get("version") {
+        None => Ok(1),
+        Some(&Value::Integer(v)) if v >= 1 && v <= CONFIG_VERSION => Ok(v),
+        Some(v) => Err(GliError::Config(format!(
+            "unsupported version {}, this gli understands versions up to {}",
+            format_value(v),
+            CONFIG_VERSION
+        ))),
+    }
+}
+
+/// Applies every migration step between the file version and `CONFIG_VERSION`
+pub fn migrate_config(mut value: Value) -> Result<Value, GliError> {
+    let mut version = config_version(&value)?;
+    while version < CONFIG_VERSION {
+        value = match version {
+            1 => migrate_v1(value)?,
+            2 => migrate_v2(value)?,
+            _ => unreachable!(),
+        };
+        version += 1;
+    }
+    Ok(value)
+}
+
+#[derive(Deserialize)]
+struct ConfigV1 {
+    gitlab_domain: String,
+    gitlab_token:  String,
+    github_token:  String,
+}
+
+fn migrate_v1(value: Value) -> Result<Value, GliError> {
+    let old: ConfigV1 = value.try_into()?;
+    let config = Config {
+        version:           2,
+        branch_pattern:    None,
+        in_progress_label: None,
+        commit_policy:     None,
+        context_commands:  None,
+        accounts:          vec![
+            Account::new(Forge::Gitlab, &old.gitlab_domain, &old.gitlab_token),
+            Account::new(Forge::Github, "github.com", &old.github_token),
+        ],
+    };
+    Ok(Value::try_from(config)?)
+}
+
+/// `insecure` only ever disabled hostname verification, version 3 names it after what it does
+fn migrate_v2(mut value: Value) -> Result<Value, GliError> {
+    if let Some(&mut Value::Array(ref mut accounts)) = value.get_mut("accounts") {
+        for account in accounts.iter_mut() {
+            if let Some(table) = account.as_table_mut() {
+                if let Some(insecure) = table.remove("insecure") {
+                    table.insert("skip_hostname_verification".to_owned(), insecure);
+                }
+            }
+        }
+    }
+    if let Some(table) = value.as_table_mut() {
+        table.insert("version".to_owned(), Value::Integer(3));
+    }
+    Ok(value)
+}
+
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').fold(Some(value), |v, segment| match v {
+        Some(&Value::Array(ref a)) => segment.parse::<usize>().ok().and_then(|i| a.get(i)),
+        Some(v) => v.get(segment),
+        None => None,
+    })
+}
+
+fn lookup_mut<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    key.split('.').fold(Some(value), |v, segment| match v {
+        Some(&mut Value::Array(ref mut a)) => segment.parse::<usize>().ok().and_then(move |i| a.get_mut(i)),
+        Some(v) => v.get_mut(segment),
+        None => None,
+    })
+}
+
+/// Settings holding a list, set from comma separated values
+const LIST_KEYS: &'static [&'static str] = &["context_commands"];
+
+/// `rustc -V, cargo -V` becomes `["rustc -V", "cargo -V"]`, an empty string an empty list
+fn parse_list(raw: &str) -> Value {
+    Value::Array(
+        raw.split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+            .map(|item| Value::String(item.to_owned()))
+            .collect(),
+    )
+}
+
+/// Optional settings are not written in the file until they're set, so `gli config set`
+/// has to create them. Unknown keys are refused when the result is deserialized.
+fn insert_optional_key(value: &mut Value, key: &str, raw: &str) -> Result<(), GliError> {
+    let (parent, name) = match key.rfind('.') {
+        Some(i) => (&key[..i], &key[i + 1..]),
+        None => ("", key),
+    };
+    let table = if parent.is_empty() {
+        value.as_table_mut()
+    } else {
+        lookup_mut(value, parent).and_then(|v| v.as_table_mut())
+    };
+    let new_value = match raw {
+        _ if LIST_KEYS.contains(&key) => parse_list(raw),
+        "true" | "false" => Value::Boolean(raw == "true"),
+        _ => Value::String(raw.to_owned()),
+    };
+    match table {
+        Some(t) => {
+            t.insert(name.to_owned(), new_value);
+            Ok(())
+        }
+        None => Err(format!("Unknown configuration key `{}`", key).into()),
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        &Value::String(ref s) => s.clone(),
+        &Value::Integer(i) => i.to_string(),
+        &Value::Float(f) => f.to_string(),
+        &Value::Boolean(b) => b.to_string(),
+        &Value::Datetime(ref d) => d.to_string(),
+        v => format!("{}", v),
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    let key = |k: &str| if prefix.is_empty() { k.to_owned() } else { format!("{}.{}", prefix, k) };
+    match value {
+        &Value::Table(ref t) => for (k, v) in t {
+            flatten(&key(k), v, out)
+        },
+        &Value::Array(ref a) => for (i, v) in a.iter().enumerate() {
+            flatten(&key(&i.to_string()), v, out)
+        },
+        v => {
+            let shown = if prefix.ends_with("token") || prefix.ends_with("password") {
+                mask(&format_value(v))
+            } else {
+                format_value(v)
+            };
+            out.push((prefix.to_owned(), shown))
+        }
+    }
+}
+
+/// Shows the end of long secrets (tokens) so they can be told apart, and nothing of short ones (passwords)
+fn mask(secret: &str) -> String {
+    if secret.chars().count() < 16 {
+        return "****".to_owned();
+    }
+    let visible: String = secret.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("****{}", visible)
+}
+
+pub fn config_get(key: &str) -> Result<String, GliError> {
+    let value = read_config_value()?;
+    match lookup(&value, key) {
+        Some(&Value::Array(ref items)) if LIST_KEYS.contains(&key) => {
+            Ok(items.iter().map(format_value).collect::<Vec<_>>().join(", "))
+        }
+        Some(v) if !v.is_table() && !v.is_array() => Ok(format_value(v)),
+        Some(_) => Err(format!("`{}` is a section, try `gli config list`", key).into()),
+        None => Err(format!("Unknown configuration key `{}`", key).into()),
+    }
+}
+
+/// Changes `key` in the raw configuration, lists (`context_commands`) are given as comma separated values
+fn set_value(value: &mut Value, key: &str, raw: &str) -> Result<(), GliError> {
+    if lookup(value, key).is_none() {
+        insert_optional_key(value, key, raw)?;
+    }
+    let target = lookup_mut(value, key).ok_or_else(|| format!("Unknown configuration key `{}`", key))?;
+    *target = match *target {
+        Value::Integer(_) => Value::Integer(raw.parse().map_err(|_| format!("`{}` expects an integer", key))?),
+        Value::Boolean(_) => Value::Boolean(raw.parse().map_err(|_| format!("`{}` expects true or false", key))?),
+        Value::String(_) => Value::String(raw.to_owned()),
+        Value::Array(_) if LIST_KEYS.contains(&key) => parse_list(raw),
+        _ => return Err(format!("`{}` is a section and cannot be set directly", key).into()),
+    };
+    Ok(())
+}
+
+pub fn config_set(key: &str, raw: &str) -> Result<String, GliError> {
+    let mut value = read_config_value()?;
+    set_value(&mut value, key, raw)?;
+    let config: Config = value.clone().try_into()?;
+    let problems = config.validate();
+    if !problems.is_empty() {
+        return Err(GliError::Config(format!(
+            "refusing to save it:\n - {}",
+            problems.join("\n - ")
+        )));
+    }
+    save_config(&config)?;
+    Ok(format!("{} has been updated", key))
+}
+
+pub fn config_list() -> Result<String, GliError> {
+    let value = read_config_value()?;
+    let mut entries = vec![];
+    flatten("", &value, &mut entries);
+    Ok(entries
+        .into_iter()
+        .map(|(k, v)| format!("{} = {}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+pub fn config_validate() -> Result<String, GliError> {
+    let config = read_config()?;
+    let problems = config.validate();
+    if problems.is_empty() {
+        Ok("Configuration is valid".to_owned())
+    } else {
+        Err(GliError::Config(format!("\n - {}", problems.join("\n - "))))
+    }
+}
+
+#[cfg(test)]
+mod migration_tests {
+    use super::*;
+
+    #[test]
+    fn migrating_v1() {
+        let v1: Value = toml::from_str(
+            r#"
+gitlab_domain = "gitlab.example.org"
+gitlab_token = "gl-token"
+github_token = "gh-token"
+"#,
+        ).unwrap();
+        let config: Config = migrate_config(v1).unwrap().try_into().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.accounts.len(), 2);
+        assert_eq!(config.accounts[0].forge, Forge::Gitlab);
+        assert_eq!(config.accounts[0].domain, "gitlab.example.org");
+        assert_eq!(config.accounts[0].token, "gl-token");
+        assert_eq!(config.accounts[1].forge, Forge::Github);
+        assert_eq!(config.accounts[1].domain, "github.com");
+        assert_eq!(config.accounts[1].token, "gh-token");
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn migrating_v2() {
+        let v2: Value = toml::from_str(
+            r#"
+version = 2
+[[accounts]]
+forge = "gitlab"
+domain = "gitlab.example.org"
+token = "gl-token"
+insecure = true
+"#,
+        ).unwrap();
+        let config: Config = migrate_config(v2).unwrap().try_into().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.accounts[0].skip_hostname_verification);
+    }
+
+    #[test]
+    fn refusing_newer_versions() {
+        let future: Value = toml::from_str("version = 99\naccounts = []").unwrap();
+        assert!(migrate_config(future).is_err());
+    }
+
+    #[test]
+    fn looking_up_keys() {
+        let v: Value = toml::from_str(
+            r#"
+version = 2
+[[accounts]]
+forge = "gitlab"
+domain = "gitlab.example.org"
+token = "gl-token"
+"#,
+        ).unwrap();
+        assert_eq!(lookup(&v, "accounts.0.domain").map(format_value), Some("gitlab.example.org".to_owned()));
+        assert!(lookup(&v, "accounts.1.domain").is_none());
+        assert!(lookup(&v, "nope").is_none());
+    }
+
+    #[test]
+    fn setting_lists() {
+        let mut v: Value = toml::from_str(
+            r#"
+version = 3
+[[accounts]]
+forge = "gitlab"
+domain = "gitlab.example.org"
+token = "gl-token"
+"#,
+        ).unwrap();
+        set_value(&mut v, "context_commands", "rustc -V, cargo -V").unwrap();
+        let config: Config = v.clone().try_into().unwrap();
+        assert_eq!(config.context_commands(), &["rustc -V".to_owned(), "cargo -V".to_owned()]);
+        set_value(&mut v, "context_commands", "node -v").unwrap();
+        set_value(&mut v, "context_commands.0", "node --version").unwrap();
+        let config: Config = v.try_into().unwrap();
+        assert_eq!(config.context_commands(), &["node --version".to_owned()]);
+    }
 }
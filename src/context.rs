@@ -0,0 +1,79 @@
+use body;
+use config::*;
+use errors::GliError;
+use git;
+use std::env::consts;
+use std::process::Command;
+
+/// Output of one of the `context_commands`, stderr included
+fn run(command: &str) -> String {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(&["/C", command]).output()
+    } else {
+        Command::new("sh").args(&["-c", command]).output()
+    };
+    match output {
+        Ok(output) => {
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            if output.status.success() {
+                text.trim().to_owned()
+            } else {
+                format!("{}\n({})", text.trim(), output.status)
+            }
+        }
+        Err(e) => format!("couldn't run it: {}", e),
+    }
+}
+
+/// Collapsible Markdown section listing the facts, then each command with its output
+pub fn section(facts: &[(&str, String)], outputs: &[(String, String)]) -> String {
+    let mut text = "<details><summary>Context</summary>\n\n".to_owned();
+    for &(name, ref value) in facts {
+        text.push_str(&format!("- {}: `{}`\n", name, value));
+    }
+    for &(ref command, ref output) in outputs {
+        text.push_str(&format!("\n{}\n", body::fence(&format!("$ {}\n{}", command, output))));
+    }
+    text.push_str("\n</details>");
+    text
+}
+
+/// Commit, branch, dirty state and `git describe` of the repository, the OS,
+/// and the output of the `context_commands` setting
+pub fn gather(config: &Config) -> Result<String, GliError> {
+    let mut facts = vec![
+        ("commit", git::commit_sha(None)?),
+        ("branch", git::current_branch().unwrap_or_else(|_| "none, HEAD is detached".to_owned())),
+        ("dirty", if git::is_dirty()? { "yes" } else { "no" }.to_owned()),
+    ];
+    if let Some(description) = git::describe() {
+        facts.push(("describe", description));
+    }
+    facts.push(("os", format!("{} {}", consts::OS, consts::ARCH)));
+    let outputs: Vec<(String, String)> = config
+        .context_commands()
+        .iter()
+        .map(|c| (c.clone(), run(c)))
+        .collect();
+    Ok(section(&facts, &outputs))
+}
+
+#[cfg(test)]
+mod context_tests {
+    use super::*;
+
+    #[test]
+    fn formatting_sections() {
+        assert_eq!(
+            section(
+                &[("commit", "abc123".to_owned()), ("dirty", "no".to_owned())],
+                &[("rustc -V".to_owned(), "rustc 1.22.1".to_owned())]
+            ),
+            "<details><summary>Context</summary>\n\n- commit: `abc123`\n- dirty: `no`\n\n```\n$ rustc -V\nrustc 1.22.1\n```\n\n</details>"
+        );
+    }
+}
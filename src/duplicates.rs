@@ -0,0 +1,100 @@
+use cache;
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use rprompt::prompt_reply_stdout;
+use std::collections::HashSet;
+
+/// Titles sharing at least this proportion of words are considered similar
+const THRESHOLD: f64 = 0.4;
+const MAX_CANDIDATES: usize = 5;
+
+const STOP_WORDS: &'static [&'static str] = &[
+    "the", "and", "for", "with", "when", "from", "into", "not", "does", "doesn", "don", "can", "cannot", "should", "on", "in", "of",
+    "to", "is", "it", "an", "a", "be", "are", "at", "by", "or",
+];
+
+pub enum Decision {
+    Create,
+    Comment(u64),
+    Abort,
+}
+
+fn words(title: &str) -> HashSet<String> {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() > 1 && !STOP_WORDS.contains(w))
+        .map(|w| w.to_owned())
+        .collect()
+}
+
+/// Jaccard index of the significant words of both titles
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Open issues whose title looks like `title`, most similar first
+pub fn similar_issues<'a>(title: &str, issues: &'a [Issue]) -> Vec<(f64, &'a Issue)> {
+    let mut candidates: Vec<(f64, &Issue)> = issues
+        .iter()
+        .filter(|i| i.state == "open")
+        .map(|i| (similarity(title, &i.title), i))
+        .filter(|&(score, _)| score >= THRESHOLD)
+        .collect();
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Open issues from the forge, or from the local cache when the forge can't be reached
+fn open_issues(http: &mut HttpClient, project: &Project) -> Result<Vec<Issue>, GliError> {
+    match forge::fetch_issues(http, project, Some(&IssueFilter::Open), None) {
+        Err(GliError::Network(_)) | Err(GliError::Timeout(_)) => Ok(cache::load(project)?.issues),
+        r => r,
+    }
+}
+
+/// Shows the open issues similar to the one about to be created and asks what to do
+pub fn check(http: &mut HttpClient, project: &Project, issue: &NewIssue) -> Result<Decision, GliError> {
+    let issues = open_issues(http, project)?;
+    let candidates = similar_issues(&issue.title, &issues);
+    if candidates.is_empty() {
+        return Ok(Decision::Create);
+    }
+
+    println!("These open issues look similar:");
+    for &(_, i) in &candidates {
+        println!("  {}", i.summary());
+    }
+    loop {
+        let answer = prompt_reply_stdout("[c]reate anyway, comment on #<number> instead, or [a]bort? ")?;
+        match answer.trim().trim_left_matches('#') {
+            "c" | "create" => return Ok(Decision::Create),
+            "a" | "abort" | "" => return Ok(Decision::Abort),
+            n => match n.parse::<u64>() {
+                Ok(number) if candidates.iter().any(|&(_, i)| i.number == number) => return Ok(Decision::Comment(number)),
+                _ => println!("Please answer c, a, or the number of one of the issues above"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod similarity_tests {
+    use super::*;
+
+    #[test]
+    fn comparing_titles() {
+        assert_eq!(similarity("Crash on startup", "crash on startup!"), 1.0);
+        assert!(similarity("Crash on startup when config is missing", "Crash at startup") >= THRESHOLD);
+        assert!(similarity("Crash on startup", "Add a label command") < THRESHOLD);
+        assert_eq!(similarity("", "the"), 0.0);
+    }
+}
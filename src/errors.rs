@@ -0,0 +1,190 @@
+use git2;
+use hyper;
+use hyper_tls;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use toml;
+use xdg::BaseDirectoriesError;
+
+#[derive(Debug)]
+pub enum GliError {
+    /// `gli init` has not been run yet
+    ConfigMissing,
+    /// The configuration file exists but can't be used
+    Config(String),
+    /// The `origin` remote could not be understood
+    RemoteUnparseable(String),
+    /// The `origin` remote points to a domain without configured credentials
+    UnknownHost(String),
+    /// The forge rejected the token
+    Authentication(String),
+    /// The project, user or issue does not exist (or is not visible with this token)
+    NotFound(String),
+    /// The forge asked us to slow down, with the delay in seconds when known
+    RateLimited(Option<u64>),
+    /// The forge could not be reached
+    Network(String),
+    /// The request was sent but no response came back in time, the forge may have handled it
+    Timeout(String),
+    /// The forge refused the request content (unknown label, missing field, …)
+    ApiValidation(String),
+    Other(String),
+}
+
+impl GliError {
+    /// What the user can do about it
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            &GliError::ConfigMissing => Some("Run `gli init` so we can get going!".to_owned()),
+            &GliError::Config(_) => Some("Run `gli config validate` to see what's wrong, or `gli init` to start over".to_owned()),
+            &GliError::RemoteUnparseable(_) => Some(
+                "The `origin` remote must look like `git@<domain>:<namespace>/<project>.git` or `https://<domain>/<namespace>/<project>.git`".to_owned(),
+            ),
+            &GliError::UnknownHost(ref domain) => Some(format!(
+                "Add an account for {} in the configuration file, see `gli config list`",
+                domain
+            )),
+            &GliError::Authentication(_) => Some("Check that your personal access token is still valid and has the `api` (gitlab) or `repo` (github) scope, then update it with `gli config set`".to_owned()),
+            &GliError::NotFound(_) => Some("Check the spelling, and that your token can see this project".to_owned()),
+            &GliError::RateLimited(Some(s)) => Some(format!("Try again in {} seconds", s)),
+            &GliError::RateLimited(None) => Some("Try again in a few minutes".to_owned()),
+            &GliError::Network(_) => Some("Check your network connection".to_owned()),
+            &GliError::Timeout(_) => Some("The server may have handled the request anyway, check before trying again".to_owned()),
+            &GliError::ApiValidation(_) | &GliError::Other(_) => None,
+        }
+    }
+
+    /// Maps an unsuccessful API response to the matching error kind
+    pub fn from_api_status(status: u16, message: String) -> GliError {
+        match status {
+            401 | 403 => GliError::Authentication(message),
+            404 => GliError::NotFound(message),
+            400 | 409 | 422 => GliError::ApiValidation(message),
+            429 => GliError::RateLimited(None),
+            s if s >= 500 => GliError::Other(format!("The server failed to handle the request ({}): {}", s, message)),
+            s => GliError::Other(format!("Unexpected API response ({}): {}", s, message)),
+        }
+    }
+
+    /// Process exit code, distinct for each kind of failure so scripts can react
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            &GliError::Other(_) => 1,
+            &GliError::ConfigMissing => 3,
+            &GliError::Config(_) => 4,
+            &GliError::RemoteUnparseable(_) => 5,
+            &GliError::UnknownHost(_) => 6,
+            &GliError::Authentication(_) => 7,
+            &GliError::NotFound(_) => 8,
+            &GliError::RateLimited(_) => 9,
+            &GliError::Network(_) => 10,
+            &GliError::ApiValidation(_) => 11,
+            &GliError::Timeout(_) => 12,
+        }
+    }
+}
+
+impl fmt::Display for GliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &GliError::ConfigMissing => write!(f, "It looks like you've not configured me yet"),
+            &GliError::Config(ref e) => write!(f, "Invalid configuration: {}", e),
+            &GliError::RemoteUnparseable(ref e) => write!(f, "Couldn't parse 'origin' remote: {}", e),
+            &GliError::UnknownHost(ref domain) => write!(f, "Couldn't find credentials for {}", domain),
+            &GliError::Authentication(ref e) => write!(f, "Authentication failed: {}", e),
+            &GliError::NotFound(ref e) => write!(f, "Not found: {}", e),
+            &GliError::RateLimited(_) => write!(f, "API rate limit exceeded"),
+            &GliError::Network(ref e) => write!(f, "Network error: {}", e),
+            &GliError::Timeout(ref e) => write!(f, "Timeout: {}", e),
+            &GliError::ApiValidation(ref e) => write!(f, "The request was refused: {}", e),
+            &GliError::Other(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for GliError {
+    fn description(&self) -> &str {
+        match self {
+            &GliError::ConfigMissing => "configuration missing",
+            &GliError::Config(_) => "invalid configuration",
+            &GliError::RemoteUnparseable(_) => "unparseable remote",
+            &GliError::UnknownHost(_) => "unknown host",
+            &GliError::Authentication(_) => "authentication failure",
+            &GliError::NotFound(_) => "not found",
+            &GliError::RateLimited(_) => "rate limited",
+            &GliError::Network(_) => "network error",
+            &GliError::Timeout(_) => "timeout",
+            &GliError::ApiValidation(_) => "validation error",
+            &GliError::Other(_) => "error",
+        }
+    }
+}
+
+impl From<String> for GliError {
+    fn from(e: String) -> Self {
+        GliError::Other(e)
+    }
+}
+
+impl<'a> From<&'a str> for GliError {
+    fn from(e: &'a str) -> Self {
+        GliError::Other(e.to_owned())
+    }
+}
+
+impl From<io::Error> for GliError {
+    fn from(e: io::Error) -> Self {
+        GliError::Other(e.to_string())
+    }
+}
+
+/// `NotFound` is kept for API 404s, its hint and exit code are about the token and the project
+impl From<git2::Error> for GliError {
+    fn from(e: git2::Error) -> Self {
+        GliError::Other(e.message().to_owned())
+    }
+}
+
+impl From<toml::de::Error> for GliError {
+    fn from(e: toml::de::Error) -> Self {
+        GliError::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for GliError {
+    fn from(e: toml::ser::Error) -> Self {
+        GliError::Config(e.to_string())
+    }
+}
+
+impl From<BaseDirectoriesError> for GliError {
+    fn from(e: BaseDirectoriesError) -> Self {
+        GliError::Config(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for GliError {
+    fn from(e: serde_json::Error) -> Self {
+        GliError::Other(format!("Unexpected API response: {}", e))
+    }
+}
+
+impl From<hyper::Error> for GliError {
+    fn from(e: hyper::Error) -> Self {
+        GliError::Network(e.to_string())
+    }
+}
+
+impl From<hyper::error::UriError> for GliError {
+    fn from(e: hyper::error::UriError) -> Self {
+        GliError::Other(format!("Invalid URL: {}", e))
+    }
+}
+
+impl From<hyper_tls::Error> for GliError {
+    fn from(e: hyper_tls::Error) -> Self {
+        GliError::Network(e.to_string())
+    }
+}
@@ -0,0 +1,273 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum ExportFormat {
+    /// One JSON object per line
+    Jsonl,
+    Csv,
+    /// One file per issue, in a directory
+    Markdown,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "jsonl" | "json" => Ok(ExportFormat::Jsonl),
+            "csv" => Ok(ExportFormat::Csv),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ExportFormat::Jsonl => write!(f, "jsonl"),
+            &ExportFormat::Csv => write!(f, "csv"),
+            &ExportFormat::Markdown => write!(f, "markdown"),
+        }
+    }
+}
+
+/// An issue with everything that happened to it
+#[derive(Debug, Serialize)]
+pub struct ExportedIssue {
+    pub issue:    Issue,
+    pub comments: Vec<Comment>,
+    pub events:   Vec<IssueEvent>,
+}
+
+/// Quotes the field when it contains a comma, a quote or a line break
+pub fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn comments_text(comments: &[Comment]) -> String {
+    comments
+        .iter()
+        .map(|c| format!("{} on {}:\n{}", c.author.clone().unwrap_or("?".to_owned()), c.created_at, c.body))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn events_text(events: &[IssueEvent]) -> String {
+    events
+        .iter()
+        .map(|e| format!("{} {} {}", e.created_at, e.actor.clone().unwrap_or("?".to_owned()), e.action))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const CSV_HEADER: &'static str = "number,title,state,author,assignees,labels,milestone,created_at,updated_at,url,body,comments,events";
+
+pub fn csv_row(e: &ExportedIssue) -> String {
+    let i = &e.issue;
+    let fields = vec![
+        i.number.to_string(),
+        i.title.clone(),
+        i.state.clone(),
+        i.author.clone().unwrap_or_default(),
+        i.assignees.join(", "),
+        i.labels.join(", "),
+        i.milestone.clone().unwrap_or_default(),
+        i.created_at.clone(),
+        i.updated_at.clone(),
+        i.url.clone(),
+        i.body.clone().unwrap_or_default(),
+        comments_text(&e.comments),
+        events_text(&e.events),
+    ];
+    fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",")
+}
+
+pub fn to_markdown(e: &ExportedIssue) -> String {
+    let i = &e.issue;
+    let mut text = format!(
+        "# #{} {}\n\n- state: {}\n- author: {}\n- created: {}\n- updated: {}\n- labels: {}\n- assignees: {}\n- milestone: {}\n- url: {}\n\n{}\n",
+        i.number,
+        i.title,
+        i.state,
+        i.author.clone().unwrap_or("?".to_owned()),
+        i.created_at,
+        i.updated_at,
+        i.labels.join(", "),
+        i.assignees.join(", "),
+        i.milestone.clone().unwrap_or_default(),
+        i.url,
+        i.body.clone().unwrap_or_default()
+    );
+    if !e.comments.is_empty() {
+        text.push_str("\n## Comments\n");
+        for c in &e.comments {
+            text.push_str(&format!(
+                "\n### {} on {}\n\n{}\n",
+                c.author.clone().unwrap_or("?".to_owned()),
+                c.created_at,
+                c.body
+            ));
+        }
+    }
+    if !e.events.is_empty() {
+        text.push_str("\n## Events\n\n");
+        for line in events_text(&e.events).lines() {
+            text.push_str(&format!("- {}\n", line));
+        }
+    }
+    text
+}
+
+/// Where the issues are written as soon as they're fetched
+enum Sink {
+    /// JSONL or CSV, to a file or stdout
+    Lines(Box<Write>),
+    /// One Markdown file per issue
+    Directory(PathBuf),
+}
+
+fn write_issue(sink: &mut Sink, format: &ExportFormat, e: &ExportedIssue) -> Result<(), GliError> {
+    match sink {
+        &mut Sink::Lines(ref mut out) => match format {
+            &ExportFormat::Csv => writeln!(out, "{}", csv_row(e))?,
+            _ => writeln!(out, "{}", serde_json::to_string(e)?)?,
+        },
+        &mut Sink::Directory(ref dir) => {
+            File::create(dir.join(format!("{}.md", e.issue.number)))?.write_all(to_markdown(e).as_bytes())?
+        }
+    }
+    Ok(())
+}
+
+/// Lines of a previous JSONL export, with the number of their issue
+fn previous_lines(path: &str) -> Result<Vec<(u64, String)>, GliError> {
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    let mut lines = vec![];
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let value: Value = serde_json::from_str(line)
+            .map_err(|e| GliError::Other(format!("{} is not a JSONL export: {}", path, e)))?;
+        let number = value["issue"]["number"]
+            .as_u64()
+            .ok_or_else(|| GliError::Other(format!("{} is not a JSONL export, a line has no issue number", path)))?;
+        lines.push((number, line.to_owned()));
+    }
+    Ok(lines)
+}
+
+fn issue_with_history(http: &mut HttpClient, project: &Project, issue: Issue) -> Result<ExportedIssue, GliError> {
+    let comments = forge::fetch_comments(http, project, issue.number)?;
+    let events = forge::fetch_events(http, project, issue.number)?;
+    Ok(ExportedIssue {
+        issue:    issue,
+        comments: comments,
+        events:   events,
+    })
+}
+
+/// Exports every issue of the project (whatever its state) updated after `since`, with comments and events.
+/// Each issue is written as soon as it's fetched, so a failure doesn't lose what was already exported.
+/// A JSONL `output` is updated when `since` is given: the issues which didn't change are kept.
+/// Otherwise `since` gives the changed issues only.
+pub fn export(
+    http: &mut HttpClient,
+    project: &Project,
+    since: Option<&str>,
+    format: &ExportFormat,
+    output: Option<&str>,
+) -> Result<String, GliError> {
+    if let (&ExportFormat::Markdown, None) = (format, output) {
+        return Err(GliError::Other("Markdown exports need a directory, give it with --output".to_owned()));
+    }
+    let issues = forge::fetch_issues(http, project, None, since)?;
+    let mut previous = match (format, since, output) {
+        (&ExportFormat::Jsonl, Some(_), Some(path)) if Path::new(path).exists() => previous_lines(path)?,
+        _ => vec![],
+    };
+    // Previous versions of the changed issues, written back if they can't be exported
+    let mut replaced: HashMap<u64, String> = HashMap::new();
+    previous.retain(|&(number, ref line)| {
+        if issues.iter().any(|i| i.number == number) {
+            replaced.insert(number, line.clone());
+            false
+        } else {
+            true
+        }
+    });
+
+    let mut sink = match (format, output) {
+        (&ExportFormat::Markdown, Some(dir)) => {
+            fs::create_dir_all(dir)?;
+            Sink::Directory(PathBuf::from(dir))
+        }
+        (_, Some(path)) => Sink::Lines(Box::new(File::create(path)?)),
+        (_, None) => Sink::Lines(Box::new(io::stdout())),
+    };
+    if let &mut Sink::Lines(ref mut out) = &mut sink {
+        if let &ExportFormat::Csv = format {
+            writeln!(out, "{}", CSV_HEADER)?;
+        }
+        for &(_, ref line) in &previous {
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    let total = issues.len();
+    for (i, issue) in issues.into_iter().enumerate() {
+        eprint!("\rFetching comments and events {}/{}", i + 1, total);
+        let number = issue.number;
+        let result = issue_with_history(http, project, issue).and_then(|e| write_issue(&mut sink, format, &e));
+        if let Err(e) = result {
+            eprintln!();
+            if let Sink::Lines(ref mut out) = sink {
+                for line in replaced.values() {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            return Err(e);
+        }
+        replaced.remove(&number);
+    }
+    if total > 0 {
+        eprintln!();
+    }
+    if let Sink::Lines(ref mut out) = sink {
+        out.flush()?;
+    }
+
+    Ok(match output {
+        Some(path) if previous.is_empty() => format!("Exported {} issue(s) to {}", total, path),
+        Some(path) => format!("Exported {} issue(s) to {}, {} unchanged one(s) kept", total, path, previous.len()),
+        None => String::new(),
+    })
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+
+    #[test]
+    fn quoting_csv_fields() {
+        assert_eq!(csv_field("crash"), "crash");
+        assert_eq!(csv_field("bug, ui"), "\"bug, ui\"");
+        assert_eq!(csv_field("say \"hi\"\nthen"), "\"say \"\"hi\"\"\nthen\"");
+    }
+}
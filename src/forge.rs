@@ -0,0 +1,215 @@
+use attach;
+use attach::Attachment;
+use config::*;
+use errors::GliError;
+use github_api;
+use gitlab_api;
+use http::HttpClient;
+
+/// Creates the issue on the project's forge, returns its number and URL.
+/// The attachments are uploaded first and linked at the end of the description.
+pub fn create_issue(http: &mut HttpClient, project: &Project, issue: &NewIssue) -> Result<(u64, String), GliError> {
+    let mut issue = issue.clone();
+    issue.text = attach::with_attachments(http, project, issue.text.take(), &issue.attachments)?;
+    issue.attachments.clear();
+    let issue = &issue;
+    match &project.place {
+        &Place::Gitlab(_) => {
+            let number = gitlab_api::create_issue(http, project, issue)?;
+            let url = gitlab_api::get_issue_url(&project.account.domain, &project.name(), &number);
+            Ok((number, url))
+        }
+        &Place::Github => github_api::create_issue(http, project, issue),
+    }
+}
+
+/// Longest issue description (or comment) the forge accepts
+pub fn max_body_length(project: &Project) -> usize {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::MAX_BODY_LENGTH,
+        &Place::Github => github_api::MAX_BODY_LENGTH,
+    }
+}
+
+pub fn fetch_issues(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: Option<&IssueFilter>,
+    updated_after: Option<&str>,
+) -> Result<Vec<Issue>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::fetch_issues(http, project, filter_state, updated_after),
+        &Place::Github => github_api::fetch_issues(http, project, filter_state, updated_after),
+    }
+}
+
+pub fn get_issue(http: &mut HttpClient, project: &Project, number: u64) -> Result<Issue, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::get_issue(http, project, number),
+        &Place::Github => github_api::get_issue(http, project, number),
+    }
+}
+
+pub fn search_issues(http: &mut HttpClient, project: &Project, query: &IssueQuery) -> Result<Vec<Issue>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::search_issues(http, project, query),
+        &Place::Github => github_api::search_issues(http, project, query),
+    }
+}
+
+/// State, label and milestone changes of an issue, oldest first
+pub fn fetch_events(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<IssueEvent>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::fetch_events(http, project, number),
+        &Place::Github => github_api::fetch_events(http, project, number),
+    }
+}
+
+/// Adds a comment to an issue, returns the comment URL
+pub fn comment_issue(http: &mut HttpClient, project: &Project, number: u64, body: &str) -> Result<String, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::comment_issue(http, project, number, body),
+        &Place::Github => github_api::comment_issue(http, project, number, body),
+    }
+}
+
+/// Uploads a file, returns the Markdown linking to it
+pub fn upload_attachment(http: &mut HttpClient, project: &Project, attachment: &Attachment) -> Result<String, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::upload_attachment(http, project, attachment),
+        &Place::Github => github_api::upload_attachment(http, project, attachment),
+    }
+}
+
+pub fn set_issue_state(http: &mut HttpClient, project: &Project, number: u64, state: &IssueFilter) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::set_issue_state(http, project, number, state),
+        &Place::Github => github_api::set_issue_state(http, project, number, state),
+    }
+}
+
+pub fn add_labels(http: &mut HttpClient, project: &Project, number: u64, labels: &[String]) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::add_labels(http, project, number, labels),
+        &Place::Github => github_api::add_labels(http, project, number, labels),
+    }
+}
+
+pub fn assign_issue(http: &mut HttpClient, project: &Project, number: u64, username: &str) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::assign_issue(http, project, number, username),
+        &Place::Github => github_api::assign_issue(http, project, number, username),
+    }
+}
+
+pub fn fetch_comments(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<Comment>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::fetch_comments(http, project, number),
+        &Place::Github => github_api::fetch_comments(http, project, number),
+    }
+}
+
+/// Id of a milestone from its title (its number on github), as `NewIssue::milestone_id` expects
+pub fn milestone_id(http: &mut HttpClient, project: &Project, title: &str) -> Result<u64, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::milestone_id(http, project, title),
+        &Place::Github => github_api::milestone_id(http, project, title),
+    }
+}
+
+pub fn fetch_labels(http: &mut HttpClient, project: &Project) -> Result<Vec<Label>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::fetch_labels(http, project),
+        &Place::Github => github_api::fetch_labels(http, project),
+    }
+}
+
+pub fn create_label(http: &mut HttpClient, project: &Project, label: &Label) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::create_label(http, project, label),
+        &Place::Github => github_api::create_label(http, project, label),
+    }
+}
+
+pub fn update_label(http: &mut HttpClient, project: &Project, name: &str, update: &LabelUpdate) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::update_label(http, project, name, update),
+        &Place::Github => github_api::update_label(http, project, name, update),
+    }
+}
+
+pub fn delete_label(http: &mut HttpClient, project: &Project, name: &str) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::delete_label(http, project, name),
+        &Place::Github => github_api::delete_label(http, project, name),
+    }
+}
+
+/// Whether the token can see the project: the forges answer 404 for hidden projects as for missing issues
+pub fn project_exists(http: &mut HttpClient, project: &Project) -> Result<bool, GliError> {
+    let result = match &project.place {
+        &Place::Gitlab(_) => gitlab_api::check_project(http, project),
+        &Place::Github => github_api::check_project(http, project),
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(GliError::NotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn default_branch(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::default_branch(http, project),
+        &Place::Github => github_api::default_branch(http, project),
+    }
+}
+
+/// Opens a merge request (pull request on github), returns its number and URL
+pub fn create_merge_request(http: &mut HttpClient, project: &Project, mr: &NewMergeRequest) -> Result<(u64, String), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::create_merge_request(http, project, mr),
+        &Place::Github => github_api::create_merge_request(http, project, mr),
+    }
+}
+
+pub fn fetch_merge_requests(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: &MergeRequestFilter,
+    author: Option<&str>,
+    reviewer: Option<&str>,
+) -> Result<Vec<MergeRequest>, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::fetch_merge_requests(http, project, filter_state, author, reviewer),
+        &Place::Github => github_api::fetch_merge_requests(http, project, filter_state, author, reviewer),
+    }
+}
+
+pub fn get_merge_request(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequest, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::get_merge_request(http, project, number),
+        &Place::Github => github_api::get_merge_request(http, project, number),
+    }
+}
+
+pub fn merge_request_status(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequestStatus, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::merge_request_status(http, project, number),
+        &Place::Github => github_api::merge_request_status(http, project, number),
+    }
+}
+
+pub fn merge_merge_request(http: &mut HttpClient, project: &Project, number: u64, method: &MergeMethod) -> Result<(), GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::merge_merge_request(http, project, number, method),
+        &Place::Github => github_api::merge_merge_request(http, project, number, method),
+    }
+}
+
+pub fn current_user(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    match &project.place {
+        &Place::Gitlab(_) => gitlab_api::current_user(http, project),
+        &Place::Github => github_api::current_user(http, project),
+    }
+}
@@ -0,0 +1,139 @@
+use errors::GliError;
+use git2::{BranchType, Cred, DescribeFormatOptions, DescribeOptions, FetchOptions, RemoteCallbacks, Repository, StatusOptions};
+use std::process::Command;
+
+/// The repository containing the current directory
+pub fn open_repository() -> Result<Repository, GliError> {
+    Ok(Repository::discover(".")?)
+}
+
+/// Name of the checked out branch
+pub fn current_branch() -> Result<String, GliError> {
+    let repo = open_repository()?;
+    let head = repo.head()?;
+    match head.shorthand() {
+        Some(name) if head.is_branch() => Ok(name.to_owned()),
+        _ => Err("HEAD is detached, not on a branch".into()),
+    }
+}
+
+/// Full hash of a revision, `HEAD` by default
+pub fn commit_sha(revision: Option<&str>) -> Result<String, GliError> {
+    let repo = open_repository()?;
+    let object = repo.revparse_single(revision.unwrap_or("HEAD"))?;
+    Ok(object.id().to_string())
+}
+
+/// Whether tracked files have uncommitted changes
+pub fn is_dirty() -> Result<bool, GliError> {
+    let repo = open_repository()?;
+    let mut options = StatusOptions::new();
+    options.include_untracked(false);
+    let dirty = !repo.statuses(Some(&mut options))?.is_empty();
+    Ok(dirty)
+}
+
+/// `git describe --tags --dirty`, `None` when no tag is reachable from HEAD
+pub fn describe() -> Option<String> {
+    let repo = match open_repository() {
+        Ok(repo) => repo,
+        Err(_) => return None,
+    };
+    let mut options = DescribeOptions::new();
+    options.describe_tags();
+    let mut format = DescribeFormatOptions::new();
+    format.dirty_suffix("-dirty");
+    let description = repo.describe(&options).and_then(|d| d.format(Some(&format)));
+    description.ok()
+}
+
+/// Creates a branch at HEAD and switches to it
+pub fn create_branch(branch: &str) -> Result<(), GliError> {
+    let repo = open_repository()?;
+    if repo.find_branch(branch, BranchType::Local).is_ok() {
+        return Err(format!("Branch {} already exists", branch).into());
+    }
+    let commit = repo.find_commit(repo.refname_to_id("HEAD")?)?;
+    repo.branch(branch, &commit, false)?;
+    repo.set_head(&format!("refs/heads/{}", branch))?;
+    Ok(())
+}
+
+/// Messages of the commits of HEAD which aren't in `base` (looked up on `origin` first), oldest first
+pub fn commit_messages_since(base: &str) -> Result<Vec<String>, GliError> {
+    let repo = open_repository()?;
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+    walk.hide_ref(&format!("refs/remotes/origin/{}", base))
+        .or_else(|_| walk.hide_ref(&format!("refs/heads/{}", base)))
+        .map_err(|_| GliError::NotFound(format!("branch {}", base)))?;
+    let mut messages = vec![];
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        messages.push(commit.message().unwrap_or("").trim().to_owned());
+    }
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Fetches `remote_ref` from `origin` (into `refs/remotes/origin/…`) and checks it out as the new local
+/// branch `branch`. Credentials come from the ssh agent or git's credential helpers.
+pub fn checkout_remote_ref(remote_ref: &str, branch: &str) -> Result<(), GliError> {
+    let repo = open_repository()?;
+    if repo.find_branch(branch, BranchType::Local).is_ok() {
+        return Err(format!("Branch {} already exists, choose another name with --branch", branch).into());
+    }
+    let tracking = format!("refs/remotes/origin/{}", remote_ref.trim_left_matches("refs/"));
+    let refspec = format!("+{}:{}", remote_ref, tracking);
+    {
+        let config = repo.config()?;
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(|url, username, _| {
+            if url.starts_with("http") {
+                Cred::credential_helper(&config, url, username)
+            } else {
+                Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            }
+        });
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(callbacks);
+        repo.find_remote("origin")?
+            .fetch(&[refspec.as_str()], Some(&mut options), None)?;
+    }
+    let commit = repo.find_commit(repo.refname_to_id(&tracking)?)?;
+    repo.branch(branch, &commit, false)?;
+    repo.checkout_tree(commit.as_object(), None)?;
+    repo.set_head(&format!("refs/heads/{}", branch))?;
+    Ok(())
+}
+
+/// Author of a line of a file (relative to the repository root), `None` when it isn't committed yet
+pub fn blame_author(path: &str, line: usize) -> Result<Option<String>, GliError> {
+    let repo = open_repository()?;
+    let workdir = repo.workdir()
+        .ok_or_else(|| GliError::Other("This is a bare repository".to_owned()))?;
+    let output = Command::new("git")
+        .current_dir(workdir)
+        .args(&["blame", "--porcelain", "-L", &format!("{},{}", line, line), "--", path])
+        .output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|l| l.starts_with("author "))
+        .map(|l| l["author ".len()..].to_owned())
+        .and_then(|author| if author == "Not Committed Yet" { None } else { Some(author) }))
+}
+
+/// Pushes the branch to `origin` and sets it as upstream. This runs `git` itself so that
+/// the user's credential helpers and ssh agent are used.
+pub fn push(branch: &str) -> Result<(), GliError> {
+    let status = Command::new("git")
+        .args(&["push", "--set-upstream", "origin", branch])
+        .status()?;
+    if !status.success() {
+        return Err(format!("git push exited with {}", status).into());
+    }
+    Ok(())
+}
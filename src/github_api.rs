@@ -1,8 +1,25 @@
+use attach::Attachment;
+use base64;
 use config::*;
-use gh::client::{Executor, Github};
-use open;
-use std::error::Error;
+use errors::GliError;
+use http::{HttpClient, Response};
+use hyper::{Delete, Get, Method, Post, Put};
+use hyper::Method::Patch;
+use hyper::header::Headers;
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::result::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+
+const API_ROOT: &'static str = "https://api.github.com";
+const PER_PAGE: u32 = 100;
+/// Characters in an issue body or a comment
+pub const MAX_BODY_LENGTH: usize = 65_536;
+/// Branch holding the attachments which can't go to a gist
+const ATTACHMENTS_BRANCH: &'static str = "gli-attachments";
 
 #[derive(Debug, Serialize)]
 struct IssueData {
@@ -10,6 +27,8 @@ struct IssueData {
     body:      Option<String>,
     labels:    Vec<String>,
     assignees: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milestone: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,79 +37,886 @@ struct IssueCreationData {
     html_url: String,
 }
 
-pub fn create_issue(
-    config: &Config,
-    project: &Project,
-    title: &str,
-    text: &Option<String>,
-    labels: &Vec<String>,
-    assignee: &Option<String>,
-) -> Result<(u64, String), Box<Error>> {
-    let mut assignees: Vec<String> = vec![];
-    if let &Some(ref a) = assignee {
-        assignees.push(a.clone());
+#[derive(Debug, Deserialize)]
+struct GhIssue {
+    number:       u64,
+    html_url:     String,
+    title:        String,
+    body:         Option<String>,
+    created_at:   String,
+    updated_at:   String,
+    state:        String,
+    labels:       Vec<GhLabel>,
+    user:         Option<GhUser>,
+    #[serde(default)]
+    assignees:    Vec<GhUser>,
+    pull_request: Option<Value>,
+    milestone:    Option<GhMilestoneRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhMilestoneRef {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhLabel {
+    name: String,
+}
+
+impl From<GhIssue> for Issue {
+    fn from(i: GhIssue) -> Issue {
+        Issue {
+            number:       i.number,
+            title:        i.title,
+            body:         i.body,
+            state:        i.state,
+            labels:       i.labels.into_iter().map(|l| l.name).collect(),
+            author:       i.user.map(|u| u.login),
+            assignees:    i.assignees.into_iter().map(|u| u.login).collect(),
+            created_at:   i.created_at,
+            updated_at:   i.updated_at,
+            url:          i.html_url,
+            milestone:    i.milestone.map(|m| m.title),
+            confidential: false,
+        }
+    }
+}
+
+/// Calls the github v3 API, `path` is relative to `https://api.github.com`
+fn api_call(http: &mut HttpClient, account: &Account, method: Method, path: &str, body: Option<String>) -> Result<Response, GliError> {
+    let mut headers = Headers::new();
+    headers.set_raw("Authorization", format!("token {}", account.token));
+    headers.set_raw("Accept", "application/vnd.github.v3+json");
+    let res = http.send(method, &format!("{}{}", API_ROOT, path), headers, body.map(String::into_bytes))?;
+    if !res.status.is_success() {
+        let body: Value = res.json().unwrap_or(Value::Null);
+        return Err(api_error(res.status.as_u16(), &body));
+    }
+    Ok(res)
+}
+
+/// Fetches pages until github's `Link` header has no `rel="next"` anymore
+fn get_all<T>(http: &mut HttpClient, account: &Account, path: &str) -> Result<Vec<T>, GliError>
+where
+    T: ::serde::de::DeserializeOwned,
+{
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let mut items = vec![];
+    let mut page = 1;
+    loop {
+        let res = api_call(
+            http,
+            account,
+            Get,
+            &format!("{}{}per_page={}&page={}", path, separator, PER_PAGE, page),
+            None,
+        )?;
+        let mut batch: Vec<T> = res.json()?;
+        items.append(&mut batch);
+        if !res.header("Link").map(|l| l.contains("rel=\"next\"")).unwrap_or(false) {
+            return Ok(items);
+        }
+        page += 1;
+    }
+}
+
+pub fn create_issue(http: &mut HttpClient, project: &Project, issue: &NewIssue) -> Result<(u64, String), GliError> {
+    if issue.confidential || issue.due_date.is_some() || issue.weight.is_some() || issue.epic_id.is_some()
+        || issue.issue_type.is_some()
+    {
+        eprintln!("WARNING: confidential, due date, weight, epic and type are only supported on gitlab");
     }
     let data = IssueData {
-        title:     title.to_owned(),
-        body:      text.clone(),
-        labels:    labels.clone(),
-        assignees: assignees,
+        title:     issue.title.clone(),
+        body:      issue.text.clone(),
+        labels:    issue.labels.clone(),
+        assignees: issue.assignee.iter().cloned().collect(),
+        milestone: issue.milestone_id,
     };
-    let client = Github::new(&config.github_token)?;
-    let (_, _, result) = client
-        .post(data)
-        .repos()
-        .owner(&project.owner)
-        .repo(&project.repo)
-        .issues()
-        .execute::<IssueCreationData>()?;
-
-    if let Some(d) = result {
-        Ok((d.number, d.html_url))
-    } else {
-        Err(format!("Error parsing github API response").into())
-    }
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/issues", project.owner, project.repo),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    let d: IssueCreationData = res.json()?;
+    Ok((d.number, d.html_url))
+}
+
+#[derive(Debug, Serialize)]
+struct CommentData<'a> {
+    body: &'a str,
 }
 
 #[derive(Debug, Deserialize)]
-struct GhIssue {
-    number:     u64,
+struct GhComment {
     html_url:   String,
-    title:      String,
+    body:       String,
+    user:       Option<GhUser>,
     created_at: String,
-    state:      String,
-}
-
-pub fn list_issues(config: Config, project: &Project, filter_state: &IssueFilter) -> Result<String, Box<Error>> {
-    if filter_state != &IssueFilter::Open {
-        println!("WARNING: Only open issues are currently returned by the API");
-    }
-    let client = Github::new(&config.github_token)?;
-    let (_, _, issues) = client
-        .get()
-        .repos()
-        .owner(&project.owner)
-        .repo(&project.repo)
-        .issues()
-        .execute::<Vec<GhIssue>>()?;
-    issues
-        .unwrap_or(vec![])
+}
+
+#[derive(Debug, Serialize)]
+struct StateData<'a> {
+    state: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct LabelsData<'a> {
+    labels: &'a [String],
+}
+
+#[derive(Debug, Serialize)]
+struct AssigneesData<'a> {
+    assignees: Vec<&'a str>,
+}
+
+pub fn set_issue_state(http: &mut HttpClient, project: &Project, number: u64, state: &IssueFilter) -> Result<(), GliError> {
+    api_call(
+        http,
+        &project.account,
+        Patch,
+        &format!("/repos/{}/{}/issues/{}", project.owner, project.repo, number),
+        Some(serde_json::to_string(&StateData { state: &state.to_string() })?),
+    )?;
+    Ok(())
+}
+
+pub fn add_labels(http: &mut HttpClient, project: &Project, number: u64, labels: &[String]) -> Result<(), GliError> {
+    api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/issues/{}/labels", project.owner, project.repo, number),
+        Some(serde_json::to_string(&LabelsData { labels })?),
+    )?;
+    Ok(())
+}
+
+/// Replaces the assignees of the issue with `username`
+pub fn assign_issue(http: &mut HttpClient, project: &Project, number: u64, username: &str) -> Result<(), GliError> {
+    api_call(
+        http,
+        &project.account,
+        Patch,
+        &format!("/repos/{}/{}/issues/{}", project.owner, project.repo, number),
+        Some(serde_json::to_string(&AssigneesData { assignees: vec![username] })?),
+    )?;
+    Ok(())
+}
+
+/// Comments of an issue, oldest first
+pub fn fetch_comments(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<Comment>, GliError> {
+    let comments: Vec<GhComment> = get_all(
+        http,
+        &project.account,
+        &format!("/repos/{}/{}/issues/{}/comments", project.owner, project.repo, number),
+    )?;
+    Ok(comments
+        .into_iter()
+        .map(|c| Comment {
+            author:     c.user.map(|u| u.login),
+            body:       c.body,
+            created_at: c.created_at,
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+struct GhEvent {
+    actor:      Option<GhUser>,
+    event:      String,
+    created_at: String,
+    label:      Option<GhLabel>,
+    milestone:  Option<GhMilestoneRef>,
+}
+
+/// Timeline events (closed, labeled, assigned, …), oldest first
+pub fn fetch_events(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<IssueEvent>, GliError> {
+    let events: Vec<GhEvent> = get_all(
+        http,
+        &project.account,
+        &format!("/repos/{}/{}/issues/{}/events", project.owner, project.repo, number),
+    )?;
+    Ok(events
+        .into_iter()
+        .map(|e| {
+            let label = e.label.map(|l| l.name).unwrap_or("?".to_owned());
+            let milestone = e.milestone.map(|m| m.title).unwrap_or("?".to_owned());
+            IssueEvent {
+                actor:      e.actor.map(|u| u.login),
+                action:     match e.event.as_str() {
+                    "labeled" => format!("added label {}", label),
+                    "unlabeled" => format!("removed label {}", label),
+                    "milestoned" => format!("added milestone {}", milestone),
+                    "demilestoned" => format!("removed milestone {}", milestone),
+                    event => event.replace('_', " "),
+                },
+                created_at: e.created_at,
+            }
+        })
+        .collect())
+}
+
+/// Adds a comment to an issue, returns the comment URL
+pub fn comment_issue(http: &mut HttpClient, project: &Project, number: u64, body: &str) -> Result<String, GliError> {
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/issues/{}/comments", project.owner, project.repo, number),
+        Some(serde_json::to_string(&CommentData { body })?),
+    )?;
+    let comment: GhComment = res.json()?;
+    Ok(comment.html_url)
+}
+
+#[derive(Debug, Serialize)]
+struct GistData<'a> {
+    description: String,
+    public:      bool,
+    files:       HashMap<&'a str, GistFile<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct GistFile<'a> {
+    content: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhGist {
+    html_url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RefData {
+    #[serde(rename = "ref")]
+    reference: String,
+    sha:       String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhRef {
+    object: GhObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhObject {
+    sha: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ContentData<'a> {
+    message: String,
+    content: String,
+    branch:  &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhContentCreated {
+    content: GhContent,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhContent {
+    html_url: String,
+}
+
+/// Github has no upload API: text files go to a secret gist, other files (or all of them when the
+/// token can't create gists) are committed to the `gli-attachments` branch if the account allows it
+/// with `commit_attachments`. Returns the Markdown linking to the file.
+pub fn upload_attachment(http: &mut HttpClient, project: &Project, attachment: &Attachment) -> Result<String, GliError> {
+    if let Some(text) = attachment.text() {
+        let mut files = HashMap::new();
+        files.insert(attachment.name.as_str(), GistFile { content: text });
+        let data = GistData {
+            description: format!("Attached to {} by gli", project.name()),
+            public:      false,
+            files:       files,
+        };
+        match api_call(http, &project.account, Post, "/gists", Some(serde_json::to_string(&data)?)) {
+            Ok(res) => {
+                let gist: GhGist = res.json()?;
+                return Ok(attachment.link(&gist.html_url));
+            }
+            Err(GliError::NotFound(_)) | Err(GliError::Authentication(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    commit_attachment(http, project, attachment)
+}
+
+fn create_attachments_branch(http: &mut HttpClient, project: &Project) -> Result<(), GliError> {
+    let base = default_branch(http, project)?;
+    let head: GhRef = api_call(
+        http,
+        &project.account,
+        Get,
+        &format!("/repos/{}/{}/git/ref/heads/{}", project.owner, project.repo, base),
+        None,
+    )?.json()?;
+    let data = RefData {
+        reference: format!("refs/heads/{}", ATTACHMENTS_BRANCH),
+        sha:       head.object.sha,
+    };
+    api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/git/refs", project.owner, project.repo),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
+}
+
+fn commit_attachment(http: &mut HttpClient, project: &Project, attachment: &Attachment) -> Result<String, GliError> {
+    if !project.account.commit_attachments {
+        return Err(GliError::Other(format!(
+            "Github can't host {} as a gist. It can be committed to a `{}` branch of {}, which needs push access \
+             and makes it public on a public repository: enable it with `gli config set accounts.<n>.commit_attachments true`",
+            attachment.name,
+            ATTACHMENTS_BRANCH,
+            project.name()
+        )));
+    }
+    let branch_path = format!("/repos/{}/{}/git/ref/heads/{}", project.owner, project.repo, ATTACHMENTS_BRANCH);
+    match api_call(http, &project.account, Get, &branch_path, None) {
+        Ok(_) => {}
+        Err(GliError::NotFound(_)) => create_attachments_branch(http, project)?,
+        Err(e) => return Err(e),
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let name: String = attachment
+        .name
+        .chars()
+        .map(|c| if (c.is_alphanumeric() && (c as u32) < 128) || c == '.' || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let data = ContentData {
+        message: format!("Attach {}", attachment.name),
+        content: base64::encode(&attachment.content),
+        branch:  ATTACHMENTS_BRANCH,
+    };
+    let res = api_call(
+        http,
+        &project.account,
+        Put,
+        &format!("/repos/{}/{}/contents/attachments/{}-{}", project.owner, project.repo, timestamp, name),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    let created: GhContentCreated = res.json()?;
+    Ok(attachment.link(&format!("{}?raw=true", created.content.html_url)))
+}
+
+#[derive(Debug, Deserialize)]
+struct GhMilestone {
+    number: u64,
+    title:  String,
+}
+
+/// Number of the repository's milestone with that title
+pub fn milestone_id(http: &mut HttpClient, project: &Project, title: &str) -> Result<u64, GliError> {
+    let path = format!("/repos/{}/{}/milestones?state=all", project.owner, project.repo);
+    let milestones: Vec<GhMilestone> = get_all(http, &project.account, &path)?;
+    milestones
+        .iter()
+        .find(|m| m.title == title)
+        .map(|m| m.number)
+        .ok_or_else(|| GliError::NotFound(format!("milestone {}", title)))
+}
+
+#[derive(Debug, Deserialize)]
+struct GhLabelDetails {
+    name:        String,
+    color:       String,
+    description: Option<String>,
+}
+
+/// Github writes colors without the `#`
+impl From<GhLabelDetails> for Label {
+    fn from(l: GhLabelDetails) -> Label {
+        Label {
+            name:        l.name,
+            color:       format!("#{}", l.color),
+            description: l.description,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LabelData<'a> {
+    name:        &'a str,
+    color:       &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a String>,
+}
+
+pub fn fetch_labels(http: &mut HttpClient, project: &Project) -> Result<Vec<Label>, GliError> {
+    let labels: Vec<GhLabelDetails> = get_all(
+        http,
+        &project.account,
+        &format!("/repos/{}/{}/labels", project.owner, project.repo),
+    )?;
+    Ok(labels.into_iter().map(Label::from).collect())
+}
+
+pub fn create_label(http: &mut HttpClient, project: &Project, label: &Label) -> Result<(), GliError> {
+    let data = LabelData {
+        name:        &label.name,
+        color:       label.color.trim_left_matches('#'),
+        description: label.description.as_ref(),
+    };
+    api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/labels", project.owner, project.repo),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct LabelUpdateData<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_name:    Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color:       Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a String>,
+}
+
+fn label_path(project: &Project, name: &str) -> String {
+    format!(
+        "/repos/{}/{}/labels/{}",
+        project.owner,
+        project.repo,
+        utf8_percent_encode(name, PATH_SEGMENT_ENCODE_SET)
+    )
+}
+
+pub fn update_label(http: &mut HttpClient, project: &Project, name: &str, update: &LabelUpdate) -> Result<(), GliError> {
+    let data = LabelUpdateData {
+        new_name:    update.new_name.as_ref(),
+        color:       update.color.as_ref().map(|c| c.trim_left_matches('#')),
+        description: update.description.as_ref(),
+    };
+    api_call(
+        http,
+        &project.account,
+        Patch,
+        &label_path(project, name),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
+}
+
+pub fn delete_label(http: &mut HttpClient, project: &Project, name: &str) -> Result<(), GliError> {
+    api_call(http, &project.account, Delete, &label_path(project, name), None)?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct GhRepository {
+    default_branch: String,
+}
+
+/// Errors out, with `NotFound` when the token can't see the repository
+pub fn check_project(http: &mut HttpClient, project: &Project) -> Result<(), GliError> {
+    api_call(http, &project.account, Get, &format!("/repos/{}/{}", project.owner, project.repo), None)?;
+    Ok(())
+}
+
+pub fn default_branch(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let path = format!("/repos/{}/{}", project.owner, project.repo);
+    let repository: GhRepository = api_call(http, &project.account, Get, &path, None)?.json()?;
+    Ok(repository.default_branch)
+}
+
+#[derive(Debug, Serialize)]
+struct PullRequestData<'a> {
+    title: &'a str,
+    head:  &'a str,
+    base:  &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body:  Option<&'a String>,
+    draft: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ReviewersData<'a> {
+    reviewers: &'a [String],
+}
+
+/// Opens the pull request, returns its number and URL. Labels, assignees and reviewers
+/// can't be given on creation and need one more call each.
+pub fn create_merge_request(http: &mut HttpClient, project: &Project, mr: &NewMergeRequest) -> Result<(u64, String), GliError> {
+    let data = PullRequestData {
+        title: &mr.title,
+        head:  &mr.source_branch,
+        base:  &mr.target_branch,
+        body:  mr.body.as_ref(),
+        draft: mr.draft,
+    };
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("/repos/{}/{}/pulls", project.owner, project.repo),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    let created: IssueCreationData = res.json()?;
+    if !mr.labels.is_empty() {
+        add_labels(http, project, created.number, &mr.labels)?;
+    }
+    if let Some(ref assignee) = mr.assignee {
+        assign_issue(http, project, created.number, assignee)?;
+    }
+    if !mr.reviewers.is_empty() {
+        api_call(
+            http,
+            &project.account,
+            Post,
+            &format!(
+                "/repos/{}/{}/pulls/{}/requested_reviewers",
+                project.owner, project.repo, created.number
+            ),
+            Some(serde_json::to_string(&ReviewersData {
+                reviewers: &mr.reviewers,
+            })?),
+        )?;
+    }
+    Ok((created.number, created.html_url))
+}
+
+#[derive(Debug, Deserialize)]
+struct GhPullRequest {
+    number:              u64,
+    title:               String,
+    body:                Option<String>,
+    state:               String,
+    merged_at:           Option<String>,
+    user:                Option<GhUser>,
+    head:                GhBranchRef,
+    base:                GhBranchRef,
+    #[serde(default)]
+    draft:               bool,
+    #[serde(default)]
+    requested_reviewers: Vec<GhUser>,
+    created_at:          String,
+    updated_at:          String,
+    html_url:            String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhBranchRef {
+    #[serde(rename = "ref")]
+    name: String,
+    sha:  String,
+}
+
+impl From<GhPullRequest> for MergeRequest {
+    fn from(pr: GhPullRequest) -> MergeRequest {
+        MergeRequest {
+            number:        pr.number,
+            title:         pr.title,
+            body:          pr.body,
+            state:         if pr.merged_at.is_some() {
+                "merged".to_owned()
+            } else {
+                pr.state
+            },
+            author:        pr.user.map(|u| u.login),
+            source_branch: pr.head.name,
+            target_branch: pr.base.name,
+            sha:           pr.head.sha,
+            draft:         pr.draft,
+            reviewers:     pr.requested_reviewers.into_iter().map(|u| u.login).collect(),
+            created_at:    pr.created_at,
+            updated_at:    pr.updated_at,
+            url:           pr.html_url,
+        }
+    }
+}
+
+/// The pulls API can't filter on authors nor reviewers, this is done here.
+/// Only reviewers who haven't reviewed yet are known to github.
+pub fn fetch_merge_requests(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: &MergeRequestFilter,
+    author: Option<&str>,
+    reviewer: Option<&str>,
+) -> Result<Vec<MergeRequest>, GliError> {
+    let path = format!(
+        "/repos/{}/{}/pulls?state={}",
+        project.owner,
+        project.repo,
+        match filter_state {
+            &MergeRequestFilter::Open => "open",
+            &MergeRequestFilter::Closed | &MergeRequestFilter::Merged => "closed",
+            &MergeRequestFilter::All => "all",
+        }
+    );
+    let prs: Vec<GhPullRequest> = get_all(http, &project.account, &path)?;
+    Ok(prs.into_iter()
+        .map(MergeRequest::from)
+        .filter(|mr| match filter_state {
+            &MergeRequestFilter::Closed => mr.state == "closed",
+            &MergeRequestFilter::Merged => mr.state == "merged",
+            _ => true,
+        })
+        .filter(|mr| author.map(|a| mr.author.as_ref().map(|x| x.as_str()) == Some(a)).unwrap_or(true))
+        .filter(|mr| reviewer.map(|r| mr.reviewers.iter().any(|x| x == r)).unwrap_or(true))
+        .collect())
+}
+
+pub fn get_merge_request(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequest, GliError> {
+    let path = format!("/repos/{}/{}/pulls/{}", project.owner, project.repo, number);
+    let pr: GhPullRequest = api_call(http, &project.account, Get, &path, None)?.json()?;
+    Ok(MergeRequest::from(pr))
+}
+
+#[derive(Debug, Deserialize)]
+struct GhCheckRuns {
+    check_runs: Vec<GhCheckRun>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhCheckRun {
+    name:       String,
+    status:     String,
+    conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhCombinedStatus {
+    statuses: Vec<GhStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhStatus {
+    context: String,
+    state:   String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GhReview {
+    user:  Option<GhUser>,
+    state: String,
+}
+
+/// Check runs and commit statuses of the head commit, and who approved the pull request
+/// (only the latest review of each reviewer counts)
+pub fn merge_request_status(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequestStatus, GliError> {
+    let pr = get_merge_request(http, project, number)?;
+    let repo_path = format!("/repos/{}/{}", project.owner, project.repo);
+    let runs: GhCheckRuns = api_call(
+        http,
+        &project.account,
+        Get,
+        &format!("{}/commits/{}/check-runs", repo_path, pr.sha),
+        None,
+    )?.json()?;
+    let combined: GhCombinedStatus = api_call(
+        http,
+        &project.account,
+        Get,
+        &format!("{}/commits/{}/status", repo_path, pr.sha),
+        None,
+    )?.json()?;
+    let reviews: Vec<GhReview> = get_all(
+        http,
+        &project.account,
+        &format!("{}/pulls/{}/reviews", repo_path, number),
+    )?;
+
+    let mut checks: Vec<(String, String)> = runs.check_runs
         .into_iter()
-        .filter(|i| i.state == format!("{}", filter_state))
-        .for_each(|i| {
-            println!(
-                "#{} {} {} {} {}",
-                i.number, i.state, i.title, i.created_at, i.html_url
-            )
-        });
-
-    Ok(String::new())
-}
-
-pub fn open_project(project: &Project) -> Result<(), Box<Error>> {
-    open::that(format!(
-        "https://github.com/{}/{}",
-        &project.owner, &project.repo,
-    ))?;
+        .map(|r| (r.name, r.conclusion.unwrap_or(r.status)))
+        .collect();
+    checks.extend(combined.statuses.into_iter().map(|s| (s.context, s.state)));
+    let mut latest: HashMap<String, String> = HashMap::new();
+    for review in reviews {
+        if let (Some(user), true) = (review.user, review.state != "COMMENTED") {
+            latest.insert(user.login, review.state);
+        }
+    }
+    let mut approved_by: Vec<String> = latest
+        .into_iter()
+        .filter(|&(_, ref state)| state == "APPROVED")
+        .map(|(user, _)| user)
+        .collect();
+    approved_by.sort();
+    Ok(MergeRequestStatus {
+        checks:         checks,
+        approved_by:    approved_by,
+        approvals_left: None,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct MergeData<'a> {
+    merge_method: &'a str,
+}
+
+pub fn merge_merge_request(http: &mut HttpClient, project: &Project, number: u64, method: &MergeMethod) -> Result<(), GliError> {
+    let data = MergeData {
+        merge_method: match method {
+            &MergeMethod::Merge => "merge",
+            &MergeMethod::Squash => "squash",
+            &MergeMethod::Rebase => "rebase",
+        },
+    };
+    api_call(
+        http,
+        &project.account,
+        Put,
+        &format!("/repos/{}/{}/pulls/{}/merge", project.owner, project.repo, number),
+        Some(serde_json::to_string(&data)?),
+    )?;
     Ok(())
 }
+
+/// Turns a github error payload (`{"message": "...", "errors": [...]}`) into a readable error.
+/// Each entry of `errors` has either a `message` or a `resource`/`field`/`code` triple
+fn api_error(status: u16, body: &Value) -> GliError {
+    let message = body["message"].as_str().unwrap_or("no details given");
+    let details = body["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|e| match e["message"].as_str() {
+                    Some(m) => m.to_owned(),
+                    None => format!(
+                        "{} {} {}{}",
+                        e["resource"].as_str().unwrap_or(""),
+                        e["field"].as_str().unwrap_or(""),
+                        e["code"].as_str().unwrap_or("invalid"),
+                        e["value"]
+                            .as_str()
+                            .map(|v| format!(" ({})", v))
+                            .unwrap_or_default()
+                    ),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if details.is_empty() {
+        GliError::from_api_status(status, message.to_owned())
+    } else {
+        GliError::from_api_status(status, format!("{}: {}", message, details.join(", ")))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GhUser {
+    login: String,
+}
+
+/// Login of the owner of the token
+pub fn current_user(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let user: GhUser = api_call(http, &project.account, Get, "/user", None)?.json()?;
+    Ok(user.login)
+}
+
+/// Who we're authenticated as, and how much of the API quota is left.
+/// `/rate_limit` calls don't count against the quota
+pub fn status(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let user: GhUser = api_call(http, &project.account, Get, "/user", None)?.json()?;
+    api_call(http, &project.account, Get, "/rate_limit", None)?;
+    let quota = http.rate_limit()
+        .map(|r| r.to_string())
+        .unwrap_or("no rate limit information".to_owned());
+    Ok(format!("github.com: authenticated as {}\n{}", user.login, quota))
+}
+
+/// Fetches the project's issues (without pull requests), optionally only those in a given state
+/// or updated after a given date (ISO 8601)
+pub fn fetch_issues(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: Option<&IssueFilter>,
+    updated_after: Option<&str>,
+) -> Result<Vec<Issue>, GliError> {
+    let mut path = format!(
+        "/repos/{}/{}/issues?state={}",
+        project.owner,
+        project.repo,
+        filter_state
+            .map(|f| f.to_string())
+            .unwrap_or("all".to_owned())
+    );
+    if let Some(date) = updated_after {
+        path.push_str(&format!("&since={}", date));
+    }
+    let issues: Vec<GhIssue> = get_all(http, &project.account, &path)?;
+    Ok(issues
+        .into_iter()
+        .filter(|i| i.pull_request.is_none())
+        .map(Issue::from)
+        .collect())
+}
+
+pub fn get_issue(http: &mut HttpClient, project: &Project, number: u64) -> Result<Issue, GliError> {
+    let path = format!("/repos/{}/{}/issues/{}", project.owner, project.repo, number);
+    let issue: GhIssue = api_call(http, &project.account, Get, &path, None)?.json()?;
+    Ok(Issue::from(issue))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResults {
+    items: Vec<GhIssue>,
+}
+
+/// Uses the search API, which looks into titles, bodies and comments.
+/// Github doesn't return more than 1000 results for a search.
+pub fn search_issues(http: &mut HttpClient, project: &Project, query: &IssueQuery) -> Result<Vec<Issue>, GliError> {
+    let mut q = format!("{} repo:{}/{} is:issue", query.text, project.owner, project.repo);
+    if let Some(ref state) = query.state {
+        q.push_str(&format!(" state:{}", state));
+    }
+    for label in &query.labels {
+        q.push_str(&format!(" label:\"{}\"", label));
+    }
+    if let Some(ref author) = query.author {
+        q.push_str(&format!(" author:{}", author));
+    }
+
+    let mut issues = vec![];
+    let mut page = 1;
+    loop {
+        let res = api_call(
+            http,
+            &project.account,
+            Get,
+            &format!(
+                "/search/issues?q={}&per_page={}&page={}",
+                form_urlencoded::byte_serialize(q.as_bytes()).collect::<String>(),
+                PER_PAGE,
+                page
+            ),
+            None,
+        )?;
+        let results: SearchResults = res.json()?;
+        issues.extend(results.items.into_iter().map(Issue::from));
+        if !res.header("Link").map(|l| l.contains("rel=\"next\"")).unwrap_or(false) {
+            return Ok(issues);
+        }
+        page += 1;
+    }
+}
+
+pub fn list_issues(http: &mut HttpClient, project: &Project, filter_state: &IssueFilter) -> Result<String, GliError> {
+    let issues = fetch_issues(http, project, Some(filter_state), None)?;
+    Ok(issues
+        .iter()
+        .map(|i| i.summary())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
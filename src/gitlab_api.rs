@@ -1,163 +1,927 @@
+use attach;
+use attach::Attachment;
 use config::*;
-use config::Project;
-use futures::{Future, Stream};
-use gitlab;
-use gitlab::*;
-use gitlab::Gitlab;
-use hyper::{Chunk, Client, Post, Request};
-use hyper_tls::HttpsConnector;
-use open;
+use errors::GliError;
+use http::{HttpClient, Response};
+use hyper::{Delete, Get, Method, Post, Put};
+use hyper::header::Headers;
 use serde_json;
 use serde_json::Value;
-use std::error::Error;
-use std::fmt;
-use std::io;
 use std::result::Result;
-use std::str::FromStr;
-use tokio_core::reactor::Core;
+use std::thread;
+use std::time::Duration;
+use url::form_urlencoded;
 use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET, QUERY_ENCODE_SET};
 
-#[derive(Debug)]
-pub struct MyIssueState(IssueState);
+const PER_PAGE: u32 = 100;
+/// Characters in a description or a comment
+pub const MAX_BODY_LENGTH: usize = 1_048_576;
 
-impl FromStr for MyIssueState {
-    type Err = String;
+/// Body of `POST /projects/:id/issues`
+#[derive(Debug, Serialize)]
+struct IssueData<'a> {
+    title:        &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description:  Option<&'a String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    labels:       Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assignee_ids: Vec<u64>,
+    confidential: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_date:     Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight:       Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milestone_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    epic_id:      Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issue_type:   Option<&'a String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlIssue {
+    iid:          u64,
+    title:        String,
+    description:  Option<String>,
+    state:        String,
+    labels:       Vec<String>,
+    author:       Option<GlUserRef>,
+    #[serde(default)]
+    assignees:    Vec<GlUserRef>,
+    created_at:   String,
+    updated_at:   String,
+    web_url:      String,
+    milestone:    Option<GlMilestone>,
+    #[serde(default)]
+    confidential: bool,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_ref() {
-            "open" => Ok(MyIssueState(IssueState::Opened)),
-            "closed" => Ok(MyIssueState(IssueState::Closed)),
-            "reopened" => Ok(MyIssueState(IssueState::Reopened)),
-            _ => Err(format!("Unknown state: {}", s)),
+#[derive(Debug, Deserialize)]
+struct GlUserRef {
+    username: String,
+}
+
+impl From<GlIssue> for Issue {
+    fn from(i: GlIssue) -> Issue {
+        Issue {
+            number:       i.iid,
+            title:        i.title,
+            body:         i.description,
+            state:        display_state(&i.state).to_owned(),
+            labels:       i.labels,
+            author:       i.author.map(|a| a.username),
+            assignees:    i.assignees.into_iter().map(|a| a.username).collect(),
+            created_at:   i.created_at,
+            updated_at:   i.updated_at,
+            url:          i.web_url,
+            milestone:    i.milestone.map(|m| m.title),
+            confidential: i.confidential,
         }
     }
 }
 
-impl From<IssueState> for MyIssueState {
-    fn from(issue: IssueState) -> Self {
-        match issue {
-            IssueState::Opened => MyIssueState(IssueState::Opened),
-            IssueState::Closed => MyIssueState(IssueState::Closed),
-            IssueState::Reopened => MyIssueState(IssueState::Reopened),
+#[derive(Debug, Deserialize)]
+struct GlUser {
+    id:       u64,
+    username: String,
+}
+
+/// Calls the v4 API of the project's gitlab instance, `path` is relative to `/api/v4`
+fn api_call(http: &mut HttpClient, account: &Account, method: Method, path: &str, body: Option<String>) -> Result<Response, GliError> {
+    api_request(http, account, method, path, Headers::new(), body.map(String::into_bytes))
+}
+
+/// Like `api_call`, with extra headers and a body which isn't necessarily JSON
+fn api_request(
+    http: &mut HttpClient,
+    account: &Account,
+    method: Method,
+    path: &str,
+    mut headers: Headers,
+    body: Option<Vec<u8>>,
+) -> Result<Response, GliError> {
+    headers.set_raw("PRIVATE-TOKEN", account.token.clone());
+    let url = format!("https://{}/api/v4{}", account.domain, path);
+    let res = http.send(method, &url, headers, body)?;
+    if !res.status.is_success() {
+        return Err(api_error(res.status.as_u16(), &res.body));
+    }
+    Ok(res)
+}
+
+/// Follows gitlab's `X-Next-Page` header until every page has been fetched
+fn get_all<T>(http: &mut HttpClient, account: &Account, path: &str) -> Result<Vec<T>, GliError>
+where
+    T: ::serde::de::DeserializeOwned,
+{
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let mut items = vec![];
+    let mut page = "1".to_owned();
+    loop {
+        let res = api_call(
+            http,
+            account,
+            Get,
+            &format!("{}{}per_page={}&page={}", path, separator, PER_PAGE, page),
+            None,
+        )?;
+        let mut batch: Vec<T> = res.json()?;
+        items.append(&mut batch);
+        match res.header("X-Next-Page") {
+            Some(next) if !next.is_empty() => page = next.to_owned(),
+            _ => return Ok(items),
         }
     }
 }
 
-impl fmt::Display for MyIssueState {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.0 {
-            IssueState::Opened => write!(f, "open"),
-            IssueState::Closed => write!(f, "closed"),
-            IssueState::Reopened => write!(f, "reopened"),
+fn project_path(project: &Project) -> String {
+    format!(
+        "/projects/{}",
+        utf8_percent_encode(&project.name(), PATH_SEGMENT_ENCODE_SET)
+    )
+}
+
+pub fn create_issue(http: &mut HttpClient, project: &Project, issue: &NewIssue) -> Result<u64, GliError> {
+    let assignee_ids = match issue.assignee {
+        Some(ref a) => vec![get_user_id_by_name(http, &project.account, a)?],
+        None => vec![],
+    };
+    let data = IssueData {
+        title:        &issue.title,
+        description:  issue.text.as_ref(),
+        labels:       issue.labels.clone(),
+        assignee_ids: assignee_ids,
+        confidential: issue.confidential,
+        due_date:     issue.due_date.as_ref(),
+        weight:       issue.weight,
+        milestone_id: issue.milestone_id,
+        epic_id:      issue.epic_id,
+        issue_type:   issue.issue_type.as_ref(),
+    };
+
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("{}/issues", project_path(project)),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    let v: Value = res.json()?;
+    let id: u64 = serde_json::from_value(v["iid"].clone())?;
+    Ok(id)
+}
+
+#[derive(Debug, Serialize)]
+struct NoteData<'a> {
+    body: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlNote {
+    id:         u64,
+    body:       String,
+    author:     Option<GlUserRef>,
+    created_at: String,
+    #[serde(default)]
+    system:     bool,
+}
+
+/// Body of `PUT /projects/:id/issues/:iid`, only the given fields are changed
+#[derive(Debug, Default, Serialize)]
+struct IssueUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_event:  Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_labels:   Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignee_ids: Option<Vec<u64>>,
+}
+
+fn update_issue(http: &mut HttpClient, project: &Project, number: u64, update: &IssueUpdate) -> Result<(), GliError> {
+    api_call(
+        http,
+        &project.account,
+        Put,
+        &format!("{}/issues/{}", project_path(project), number),
+        Some(serde_json::to_string(update)?),
+    )?;
+    Ok(())
+}
+
+pub fn set_issue_state(http: &mut HttpClient, project: &Project, number: u64, state: &IssueFilter) -> Result<(), GliError> {
+    let event = match state {
+        &IssueFilter::Open => "reopen",
+        &IssueFilter::Closed => "close",
+    };
+    update_issue(
+        http,
+        project,
+        number,
+        &IssueUpdate {
+            state_event: Some(event.to_owned()),
+            ..IssueUpdate::default()
+        },
+    )
+}
+
+pub fn add_labels(http: &mut HttpClient, project: &Project, number: u64, labels: &[String]) -> Result<(), GliError> {
+    update_issue(
+        http,
+        project,
+        number,
+        &IssueUpdate {
+            add_labels: Some(labels.join(",")),
+            ..IssueUpdate::default()
+        },
+    )
+}
+
+/// Replaces the assignees of the issue with `username`
+pub fn assign_issue(http: &mut HttpClient, project: &Project, number: u64, username: &str) -> Result<(), GliError> {
+    let id = get_user_id_by_name(http, &project.account, username)?;
+    update_issue(
+        http,
+        project,
+        number,
+        &IssueUpdate {
+            assignee_ids: Some(vec![id]),
+            ..IssueUpdate::default()
+        },
+    )
+}
+
+/// Comments of an issue, oldest first
+pub fn fetch_comments(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<Comment>, GliError> {
+    let notes: Vec<GlNote> = get_all(
+        http,
+        &project.account,
+        &format!("{}/issues/{}/notes?sort=asc&order_by=created_at", project_path(project), number),
+    )?;
+    Ok(notes
+        .into_iter()
+        .filter(|n| !n.system)
+        .map(|n| Comment {
+            author:     n.author.map(|a| a.username),
+            body:       n.body,
+            created_at: n.created_at,
+        })
+        .collect())
+}
+
+/// Adds a comment to an issue, returns the comment URL
+pub fn comment_issue(http: &mut HttpClient, project: &Project, number: u64, body: &str) -> Result<String, GliError> {
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("{}/issues/{}/notes", project_path(project), number),
+        Some(serde_json::to_string(&NoteData { body })?),
+    )?;
+    let note: GlNote = res.json()?;
+    Ok(format!(
+        "{}#note_{}",
+        get_issue_url(&project.account.domain, &project.name(), &number),
+        note.id
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct GlUpload {
+    markdown: String,
+}
+
+/// Uploads a file to the project, returns the Markdown linking to it
+pub fn upload_attachment(http: &mut HttpClient, project: &Project, attachment: &Attachment) -> Result<String, GliError> {
+    let (content_type, body) = attach::multipart("file", attachment);
+    let mut headers = Headers::new();
+    headers.set_raw("Content-Type", content_type);
+    let res = api_request(
+        http,
+        &project.account,
+        Post,
+        &format!("{}/uploads", project_path(project)),
+        headers,
+        Some(body),
+    )?;
+    let upload: GlUpload = res.json()?;
+    Ok(upload.markdown)
+}
+
+#[derive(Debug, Deserialize)]
+struct GlStateEvent {
+    user:       Option<GlUserRef>,
+    state:      String,
+    created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlLabelEvent {
+    user:       Option<GlUserRef>,
+    label:      Option<GlLabelRef>,
+    action:     String,
+    created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlLabelRef {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlMilestoneEvent {
+    user:       Option<GlUserRef>,
+    milestone:  Option<GlMilestone>,
+    action:     String,
+    created_at: String,
+}
+
+/// State, label and milestone changes, oldest first. Instances without state events (before 13.2) only give the other ones.
+pub fn fetch_events(http: &mut HttpClient, project: &Project, number: u64) -> Result<Vec<IssueEvent>, GliError> {
+    let path = format!("{}/issues/{}", project_path(project), number);
+    let states: Vec<GlStateEvent> = match get_all(http, &project.account, &format!("{}/resource_state_events", path)) {
+        Err(GliError::NotFound(_)) => vec![],
+        r => r?,
+    };
+    let labels: Vec<GlLabelEvent> = get_all(http, &project.account, &format!("{}/resource_label_events", path))?;
+    let milestones: Vec<GlMilestoneEvent> = get_all(http, &project.account, &format!("{}/resource_milestone_events", path))?;
+    let action = |verb: &str, what: &str, name: Option<String>| {
+        format!(
+            "{} {} {}",
+            if verb == "add" { "added" } else { "removed" },
+            what,
+            name.unwrap_or("?".to_owned())
+        )
+    };
+    let mut events: Vec<IssueEvent> = states
+        .into_iter()
+        .map(|e| IssueEvent {
+            actor:      e.user.map(|u| u.username),
+            action:     e.state,
+            created_at: e.created_at,
+        })
+        .collect();
+    events.extend(labels.into_iter().map(|e| IssueEvent {
+        actor:      e.user.map(|u| u.username),
+        action:     action(&e.action, "label", e.label.map(|l| l.name)),
+        created_at: e.created_at,
+    }));
+    events.extend(milestones.into_iter().map(|e| IssueEvent {
+        actor:      e.user.map(|u| u.username),
+        action:     action(&e.action, "milestone", e.milestone.map(|m| m.title)),
+        created_at: e.created_at,
+    }));
+    events.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(events)
+}
+
+#[derive(Debug, Deserialize)]
+struct GlLabel {
+    name:        String,
+    color:       String,
+    description: Option<String>,
+}
+
+impl From<GlLabel> for Label {
+    fn from(l: GlLabel) -> Label {
+        Label {
+            name:        l.name,
+            color:       l.color,
+            description: l.description,
         }
     }
 }
 
-pub fn create_issue(
-    config: &Config,
-    project: &Project,
-    title: &str,
-    text: &Option<String>,
-    labels: &Vec<String>,
-    assignee: &Option<String>,
-) -> Result<u64, Box<Error>> {
-    let project_name = project.name();
-    let encoded_project = utf8_percent_encode(&project_name, PATH_SEGMENT_ENCODE_SET);
-    let encoded_title = utf8_percent_encode(title, QUERY_ENCODE_SET);
-    let desc = &text.clone().unwrap_or(String::new());
-    let encoded_desc = utf8_percent_encode(desc, QUERY_ENCODE_SET);
-    let concat = labels.join(",");
-    let encoded_labels = utf8_percent_encode(&concat, QUERY_ENCODE_SET);
-    let labels_param = if labels.len() > 0 {
-        format!("&labels={}", encoded_labels)
-    } else {
-        "".to_owned()
+#[derive(Debug, Serialize)]
+struct LabelData<'a> {
+    name:        &'a str,
+    color:       &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a String>,
+}
+
+pub fn fetch_labels(http: &mut HttpClient, project: &Project) -> Result<Vec<Label>, GliError> {
+    let labels: Vec<GlLabel> = get_all(http, &project.account, &format!("{}/labels", project_path(project)))?;
+    Ok(labels.into_iter().map(Label::from).collect())
+}
+
+pub fn create_label(http: &mut HttpClient, project: &Project, label: &Label) -> Result<(), GliError> {
+    let data = LabelData {
+        name:        &label.name,
+        color:       &label.color,
+        description: label.description.as_ref(),
     };
-    let assignee_param = if let &Some(ref a) = assignee {
-        let r = get_user_id_by_name(a)?;
-        format!("&assignee_ids={}", r.value())
-    } else {
-        String::new()
+    api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("{}/labels", project_path(project)),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct LabelUpdateData<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_name:    Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color:       Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a String>,
+}
+
+fn label_path(project: &Project, name: &str) -> String {
+    format!(
+        "{}/labels/{}",
+        project_path(project),
+        utf8_percent_encode(name, PATH_SEGMENT_ENCODE_SET)
+    )
+}
+
+pub fn update_label(http: &mut HttpClient, project: &Project, name: &str, update: &LabelUpdate) -> Result<(), GliError> {
+    let data = LabelUpdateData {
+        new_name:    update.new_name.as_ref(),
+        color:       update.color.as_ref(),
+        description: update.description.as_ref(),
     };
+    api_call(
+        http,
+        &project.account,
+        Put,
+        &label_path(project, name),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
+}
+
+pub fn delete_label(http: &mut HttpClient, project: &Project, name: &str) -> Result<(), GliError> {
+    api_call(http, &project.account, Delete, &label_path(project, name), None)?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct GlProject {
+    default_branch: Option<String>,
+    /// `merge`, `rebase_merge` or `ff`
+    #[serde(default)]
+    merge_method:   Option<String>,
+}
+
+/// Errors out, with `NotFound` when the token can't see the project
+pub fn check_project(http: &mut HttpClient, project: &Project) -> Result<(), GliError> {
+    api_call(http, &project.account, Get, &project_path(project), None)?;
+    Ok(())
+}
+
+pub fn default_branch(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let p: GlProject = api_call(http, &project.account, Get, &project_path(project), None)?.json()?;
+    p.default_branch
+        .ok_or_else(|| GliError::NotFound(format!("default branch of {}, the repository is empty", project.name())))
+}
+
+/// Body of `POST /projects/:id/merge_requests`
+#[derive(Debug, Serialize)]
+struct MergeRequestData<'a> {
+    source_branch: &'a str,
+    target_branch: &'a str,
+    title:         String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description:   Option<&'a String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    labels:        String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assignee_ids:  Vec<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reviewer_ids:  Vec<u64>,
+}
 
-    let url = format!(
-        "https://{}/api/v4/projects/{}/issues?title={}&description={}{}{}",
-        &config.gitlab_domain, encoded_project, encoded_title, encoded_desc, &labels_param, &assignee_param
+#[derive(Debug, Deserialize)]
+struct GlCreated {
+    iid:     u64,
+    web_url: String,
+}
+
+/// Opens the merge request, returns its number and URL
+pub fn create_merge_request(http: &mut HttpClient, project: &Project, mr: &NewMergeRequest) -> Result<(u64, String), GliError> {
+    let assignee_ids = match mr.assignee {
+        Some(ref a) => vec![get_user_id_by_name(http, &project.account, a)?],
+        None => vec![],
+    };
+    let mut reviewer_ids = vec![];
+    for reviewer in &mr.reviewers {
+        reviewer_ids.push(get_user_id_by_name(http, &project.account, reviewer)?);
+    }
+    let data = MergeRequestData {
+        source_branch: &mr.source_branch,
+        target_branch: &mr.target_branch,
+        title:         if mr.draft {
+            format!("Draft: {}", mr.title)
+        } else {
+            mr.title.clone()
+        },
+        description:   mr.body.as_ref(),
+        labels:        mr.labels.join(","),
+        assignee_ids:  assignee_ids,
+        reviewer_ids:  reviewer_ids,
+    };
+    let res = api_call(
+        http,
+        &project.account,
+        Post,
+        &format!("{}/merge_requests", project_path(project)),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    let created: GlCreated = res.json()?;
+    Ok((created.iid, created.web_url))
+}
+
+#[derive(Debug, Deserialize)]
+struct GlMergeRequest {
+    iid:              u64,
+    title:            String,
+    description:      Option<String>,
+    state:            String,
+    author:           Option<GlUserRef>,
+    source_branch:    String,
+    target_branch:    String,
+    sha:              Option<String>,
+    #[serde(default)]
+    draft:            bool,
+    /// `draft` was called `work_in_progress` before gitlab 13.2
+    #[serde(default)]
+    work_in_progress: bool,
+    #[serde(default)]
+    reviewers:        Vec<GlUserRef>,
+    created_at:       String,
+    updated_at:       String,
+    web_url:          String,
+}
+
+impl From<GlMergeRequest> for MergeRequest {
+    fn from(mr: GlMergeRequest) -> MergeRequest {
+        MergeRequest {
+            number:        mr.iid,
+            title:         mr.title,
+            body:          mr.description,
+            state:         display_state(&mr.state).to_owned(),
+            author:        mr.author.map(|a| a.username),
+            source_branch: mr.source_branch,
+            target_branch: mr.target_branch,
+            sha:           mr.sha.unwrap_or_default(),
+            draft:         mr.draft || mr.work_in_progress,
+            reviewers:     mr.reviewers.into_iter().map(|r| r.username).collect(),
+            created_at:    mr.created_at,
+            updated_at:    mr.updated_at,
+            url:           mr.web_url,
+        }
+    }
+}
+
+fn merge_request_path(project: &Project, number: u64) -> String {
+    format!("{}/merge_requests/{}", project_path(project), number)
+}
+
+pub fn fetch_merge_requests(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: &MergeRequestFilter,
+    author: Option<&str>,
+    reviewer: Option<&str>,
+) -> Result<Vec<MergeRequest>, GliError> {
+    let mut path = format!(
+        "{}/merge_requests?state={}",
+        project_path(project),
+        match filter_state {
+            &MergeRequestFilter::Open => "opened",
+            &MergeRequestFilter::Closed => "closed",
+            &MergeRequestFilter::Merged => "merged",
+            &MergeRequestFilter::All => "all",
+        }
     );
-    let mut core = Core::new()?;
-    let connector = HttpsConnector::new(4, &core.handle())?;
-    let client = Client::configure()
-        .connector(connector)
-        .build(&core.handle());
-
-    let uri = url.parse()?;
-    let mut request = Request::new(Post, uri);
-    request
-        .headers_mut()
-        .set_raw("PRIVATE-TOKEN", config.gitlab_token.as_str());
-
-    let work = client.request(request).and_then(|res| {
-        res.body().concat2().and_then(move |body: Chunk| {
-            let v: Value = serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            let id: u64 = serde_json::from_value(v["iid"].clone()).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            Ok(id)
-        })
-    });
-    Ok(core.run(work)?)
+    if let Some(author) = author {
+        path.push_str(&format!("&author_username={}", utf8_percent_encode(author, QUERY_ENCODE_SET)));
+    }
+    if let Some(reviewer) = reviewer {
+        path.push_str(&format!("&reviewer_username={}", utf8_percent_encode(reviewer, QUERY_ENCODE_SET)));
+    }
+    let mrs: Vec<GlMergeRequest> = get_all(http, &project.account, &path)?;
+    Ok(mrs.into_iter().map(MergeRequest::from).collect())
+}
+
+pub fn get_merge_request(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequest, GliError> {
+    let mr: GlMergeRequest = api_call(http, &project.account, Get, &merge_request_path(project, number), None)?.json()?;
+    Ok(MergeRequest::from(mr))
+}
+
+#[derive(Debug, Deserialize)]
+struct GlPipelines {
+    head_pipeline: Option<GlPipeline>,
 }
 
-fn get_user_id_by_name(name: &str) -> Result<UserId, Box<Error>> {
-    let config = read_config()?;
-    let gl = Gitlab::new(&config.gitlab_domain, &config.gitlab_token)?;
-    let user: gitlab::User = gl.user_by_name(name)?;
-    Ok(user.id)
+#[derive(Debug, Deserialize)]
+struct GlPipeline {
+    id:     u64,
+    status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlApprovals {
+    #[serde(default)]
+    approved_by:    Vec<GlApprover>,
+    approvals_left: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlApprover {
+    user: GlUserRef,
+}
+
+/// Head pipeline and approvals. Approvals aren't available on every instance, they're left empty then.
+pub fn merge_request_status(http: &mut HttpClient, project: &Project, number: u64) -> Result<MergeRequestStatus, GliError> {
+    let path = merge_request_path(project, number);
+    let pipelines: GlPipelines = api_call(http, &project.account, Get, &path, None)?.json()?;
+    let approvals = match api_call(http, &project.account, Get, &format!("{}/approvals", path), None) {
+        Ok(res) => res.json()?,
+        Err(GliError::NotFound(_)) | Err(GliError::Authentication(_)) => GlApprovals {
+            approved_by:    vec![],
+            approvals_left: None,
+        },
+        Err(e) => return Err(e),
+    };
+    Ok(MergeRequestStatus {
+        checks:         pipelines
+            .head_pipeline
+            .into_iter()
+            .map(|p| (format!("pipeline #{}", p.id), p.status))
+            .collect(),
+        approved_by:    approvals.approved_by.into_iter().map(|a| a.user.username).collect(),
+        approvals_left: approvals.approvals_left,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct MergeData {
+    squash: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlRebase {
+    #[serde(default)]
+    rebase_in_progress: bool,
+    merge_error:        Option<String>,
+}
+
+/// Rebases are asynchronous on gitlab, this waits for them to finish (for a minute at most)
+fn rebase_merge_request(http: &mut HttpClient, project: &Project, number: u64) -> Result<(), GliError> {
+    let path = merge_request_path(project, number);
+    api_call(http, &project.account, Put, &format!("{}/rebase", path), None)?;
+    for _ in 0..60 {
+        thread::sleep(Duration::from_secs(1));
+        let rebase: GlRebase = api_call(
+            http,
+            &project.account,
+            Get,
+            &format!("{}?include_rebase_in_progress=true", path),
+            None,
+        )?.json()?;
+        if !rebase.rebase_in_progress {
+            return match rebase.merge_error {
+                Some(e) => Err(GliError::ApiValidation(format!("Rebase failed: {}", e))),
+                None => Ok(()),
+            };
+        }
+    }
+    Err("The rebase is still in progress, try merging again later".into())
+}
+
+/// Gitlab rebases on request but the merge method of the project decides whether a merge commit is created:
+/// none with `ff`, one on top of the rebased commits with `rebase_merge`. `--rebase` is refused with `merge`.
+pub fn merge_merge_request(http: &mut HttpClient, project: &Project, number: u64, method: &MergeMethod) -> Result<(), GliError> {
+    if method == &MergeMethod::Rebase {
+        let p: GlProject = api_call(http, &project.account, Get, &project_path(project), None)?.json()?;
+        let merge_method = p.merge_method.unwrap_or_else(|| "merge".to_owned());
+        match merge_method.as_str() {
+            "ff" => {}
+            "rebase_merge" => eprintln!(
+                "WARNING: {} uses merge commits with semi-linear history, a merge commit will be added after the rebase",
+                project.name()
+            ),
+            _ => {
+                return Err(GliError::Other(format!(
+                    "{} creates a merge commit even after a rebase (its merge method is `{}`), \
+                     merge without --rebase or switch the project to fast-forward merges",
+                    project.name(),
+                    merge_method
+                )))
+            }
+        }
+        rebase_merge_request(http, project, number)?;
+    }
+    let data = MergeData {
+        squash: method == &MergeMethod::Squash,
+    };
+    api_call(
+        http,
+        &project.account,
+        Put,
+        &format!("{}/merge", merge_request_path(project, number)),
+        Some(serde_json::to_string(&data)?),
+    )?;
+    Ok(())
 }
 
-pub fn list_issues(config: Config, project: &Project, filter_state: &IssueFilter) -> Result<String, Box<Error>> {
-    let gitlab_client = Gitlab::new(&config.gitlab_domain, &config.gitlab_token)?;
-    let project = gitlab_client.project_by_name(project.name())?;
+/// Turns a gitlab error payload into a readable error.
+/// Gitlab answers either `{"message": "..."}`, `{"message": {"field": ["...", ...]}}`
+/// or `{"error": "...", "error_description": "...", "scope": "..."}` (oauth errors)
+fn api_error(status: u16, body: &[u8]) -> GliError {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| decode_error_message(&v))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    GliError::from_api_status(status, message)
+}
 
-    gitlab_client
-        .issues(project.id)
-        .and_then(|issues| {
-            issues
-                .into_iter()
-                .filter(|i| match filter_state {
-                    &IssueFilter::Open => (i.state == IssueState::Opened) || i.state == IssueState::Reopened,
-                    &IssueFilter::Closed => (i.state == IssueState::Closed),
+fn decode_error_message(v: &Value) -> Option<String> {
+    match (&v["message"], &v["error"]) {
+        (&Value::String(ref m), _) => Some(m.clone()),
+        (&Value::Object(ref fields), _) => Some(
+            fields
+                .iter()
+                .map(|(field, errors)| match errors {
+                    &Value::Array(ref es) => format!(
+                        "{} {}",
+                        field,
+                        es.iter()
+                            .filter_map(|e| e.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    e => format!("{} {}", field, e),
                 })
-                .for_each(|i| {
-                    println!(
-                        "#{} {} {} {} {}",
-                        i.iid,
-                        MyIssueState::from(i.state),
-                        i.title,
-                        i.created_at.format("%F %H:%M"),
-                        get_issue_url(
-                            &config.gitlab_domain,
-                            &project.path_with_namespace,
-                            &i.iid.value()
-                        )
-                    )
-                });
-            Ok("".to_string())
-        })
-        .map_err(From::from)
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+        (_, &Value::String(ref e)) => {
+            let description = v["error_description"].as_str().unwrap_or(e);
+            match v["scope"].as_str() {
+                Some(scope) => Some(format!("{} (token lacks {} scope)", description, scope)),
+                None => Some(description.to_owned()),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn get_user_id_by_name(http: &mut HttpClient, account: &Account, name: &str) -> Result<u64, GliError> {
+    let path = format!("/users?username={}", utf8_percent_encode(name, QUERY_ENCODE_SET));
+    let users: Vec<GlUser> = api_call(http, account, Get, &path, None)?.json()?;
+    users
+        .first()
+        .map(|u| u.id)
+        .ok_or_else(|| GliError::NotFound(format!("user {}", name)))
+}
+
+#[derive(Debug, Deserialize)]
+struct GlMilestone {
+    id:    u64,
+    title: String,
+}
+
+/// Id of the project's milestone with that title
+pub fn milestone_id(http: &mut HttpClient, project: &Project, title: &str) -> Result<u64, GliError> {
+    let path = format!(
+        "{}/milestones?title={}",
+        project_path(project),
+        utf8_percent_encode(title, QUERY_ENCODE_SET)
+    );
+    let milestones: Vec<GlMilestone> = api_call(http, &project.account, Get, &path, None)?.json()?;
+    milestones
+        .first()
+        .map(|m| m.id)
+        .ok_or_else(|| GliError::NotFound(format!("milestone {}", title)))
+}
+
+/// Fetches the project's issues, optionally only those in a given state
+/// or updated after a given date (ISO 8601), oldest updates first
+pub fn fetch_issues(
+    http: &mut HttpClient,
+    project: &Project,
+    filter_state: Option<&IssueFilter>,
+    updated_after: Option<&str>,
+) -> Result<Vec<Issue>, GliError> {
+    let mut path = format!(
+        "{}/issues?order_by=updated_at&sort=asc&state={}",
+        project_path(project),
+        match filter_state {
+            Some(&IssueFilter::Open) => "opened",
+            Some(&IssueFilter::Closed) => "closed",
+            None => "all",
+        }
+    );
+    if let Some(date) = updated_after {
+        path.push_str(&format!("&updated_after={}", utf8_percent_encode(date, QUERY_ENCODE_SET)));
+    }
+    let issues: Vec<GlIssue> = get_all(http, &project.account, &path)?;
+    Ok(issues.into_iter().map(Issue::from).collect())
+}
+
+pub fn get_issue(http: &mut HttpClient, project: &Project, number: u64) -> Result<Issue, GliError> {
+    let path = format!("{}/issues/{}", project_path(project), number);
+    let issue: GlIssue = api_call(http, &project.account, Get, &path, None)?.json()?;
+    Ok(Issue::from(issue))
+}
+
+/// Uses the `search` parameter of the issues endpoint, which looks into titles and descriptions
+pub fn search_issues(http: &mut HttpClient, project: &Project, query: &IssueQuery) -> Result<Vec<Issue>, GliError> {
+    let mut path = format!(
+        "{}/issues?search={}&state={}",
+        project_path(project),
+        form_urlencoded::byte_serialize(query.text.as_bytes()).collect::<String>(),
+        match query.state {
+            Some(IssueFilter::Open) => "opened",
+            Some(IssueFilter::Closed) => "closed",
+            None => "all",
+        }
+    );
+    if !query.labels.is_empty() {
+        path.push_str(&format!(
+            "&labels={}",
+            utf8_percent_encode(&query.labels.join(","), QUERY_ENCODE_SET)
+        ));
+    }
+    if let Some(ref author) = query.author {
+        path.push_str(&format!(
+            "&author_username={}",
+            utf8_percent_encode(author, QUERY_ENCODE_SET)
+        ));
+    }
+    let issues: Vec<GlIssue> = get_all(http, &project.account, &path)?;
+    Ok(issues.into_iter().map(Issue::from).collect())
+}
+
+pub fn list_issues(http: &mut HttpClient, project: &Project, filter_state: &IssueFilter) -> Result<String, GliError> {
+    let issues = fetch_issues(http, project, Some(filter_state), None)?;
+    Ok(issues
+        .iter()
+        .map(|i| i.summary())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Gitlab says `opened` (or `reopened` on old instances), we say `open` like everywhere else
+fn display_state(state: &str) -> &str {
+    match state {
+        "opened" | "reopened" => "open",
+        s => s,
+    }
+}
+
+/// Username of the owner of the token
+pub fn current_user(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let user: GlUser = api_call(http, &project.account, Get, "/user", None)?.json()?;
+    Ok(user.username)
+}
+
+/// Who we're authenticated as, and how much of the API quota is left
+pub fn status(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let user: GlUser = api_call(http, &project.account, Get, "/user", None)?.json()?;
+    let quota = http.rate_limit()
+        .map(|r| r.to_string())
+        .unwrap_or("no rate limit reported by this instance".to_owned());
+    Ok(format!(
+        "{}: authenticated as {}\n{}",
+        project.account.domain, user.username, quota
+    ))
 }
 
 pub fn get_issue_url(domain: &str, project_name: &str, number: &u64) -> String {
     format!("https://{}/{}/issues/{}", domain, project_name, number)
 }
 
-pub fn get_project_url(domain: &str, project: &Project) -> String {
-    format!("https://{}/{}", domain, project.name())
-}
 
-pub fn open_gitlab(domain: &str, p: &Project, issue: Option<u64>) -> Result<(), Box<Error>> {
-    if let Some(i) = issue {
-        open::that(get_issue_url(domain, &p.name(), &i))?;
-    } else {
-        open::that(get_project_url(domain, p))?;
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn decoding_error_messages() {
+        let plain: Value = serde_json::from_str(r#"{"message": "401 Unauthorized"}"#).unwrap();
+        assert_eq!(decode_error_message(&plain), Some("401 Unauthorized".to_owned()));
+
+        let fields: Value = serde_json::from_str(r#"{"message": {"title": ["can't be blank", "is too short"]}}"#).unwrap();
+        assert_eq!(
+            decode_error_message(&fields),
+            Some("title can't be blank, is too short".to_owned())
+        );
+
+        let scope: Value = serde_json::from_str(
+            r#"{"error": "insufficient_scope", "error_description": "The request requires higher privileges than provided by the access token.", "scope": "api"}"#,
+        ).unwrap();
+        assert_eq!(
+            decode_error_message(&scope),
+            Some("The request requires higher privileges than provided by the access token. (token lacks api scope)".to_owned())
+        );
     }
-    Ok(())
 }
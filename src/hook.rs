@@ -0,0 +1,203 @@
+use branch;
+use cache;
+use config::*;
+use errors::GliError;
+use forge;
+use git;
+use http::HttpClient;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+/// Marks the hooks written by `gli hook install`, so they can be overwritten safely
+const MARKER: &'static str = "gli hook commit-msg";
+
+const COMMIT_MSG_HOOK: &'static str = "#!/bin/sh
+# Installed by `gli hook install`, checks that the commit references an issue
+exec gli hook commit-msg \"$1\"
+";
+
+/// `core.hooksPath` if set, `.git/hooks` otherwise
+fn hooks_dir() -> Result<PathBuf, GliError> {
+    let repo = git::open_repository()?;
+    match repo.config()?.get_string("core.hooksPath") {
+        Ok(path) => Ok(repo.workdir().unwrap_or(repo.path()).join(path)),
+        Err(_) => Ok(repo.path().join("hooks")),
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &PathBuf) -> Result<(), GliError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &PathBuf) -> Result<(), GliError> {
+    Ok(())
+}
+
+/// Writes the `commit-msg` hook, an existing hook is only replaced with `force`
+pub fn install(force: bool) -> Result<String, GliError> {
+    let dir = hooks_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("commit-msg");
+    if path.exists() && !force {
+        let mut existing = String::new();
+        File::open(&path)?.read_to_string(&mut existing)?;
+        if !existing.contains(MARKER) {
+            return Err(format!("{} already exists, use --force to replace it", path.display()).into());
+        }
+    }
+    File::create(&path)?.write_all(COMMIT_MSG_HOOK.as_bytes())?;
+    make_executable(&path)?;
+    Ok(format!("Installed {}", path.display()))
+}
+
+/// Issue numbers written `#12` in the message, comment lines excluded
+pub fn issue_references(message: &str) -> Vec<u64> {
+    let mut numbers = vec![];
+    for line in message.lines().filter(|l| !l.starts_with('#')) {
+        let chars: Vec<char> = line.chars().collect();
+        for (i, &c) in chars.iter().enumerate() {
+            if c != '#' || (i > 0 && chars[i - 1].is_alphanumeric()) {
+                continue;
+            }
+            let digits: String = chars[i + 1..].iter().take_while(|c| c.is_digit(10)).cloned().collect();
+            if let Ok(n) = digits.parse() {
+                if !numbers.contains(&n) {
+                    numbers.push(n);
+                }
+            }
+        }
+    }
+    numbers
+}
+
+/// Adds `Refs #N` after the message, before the comments git adds (and the diff of `git commit -v`)
+pub fn append_reference(message: &str, number: u64) -> String {
+    let lines: Vec<&str> = message.lines().collect();
+    let scissors = lines
+        .iter()
+        .position(|l| l.starts_with('#') && l.contains(">8"))
+        .unwrap_or(lines.len());
+    let end = lines[..scissors]
+        .iter()
+        .rposition(|l| !l.starts_with('#') && !l.trim().is_empty())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let mut result: Vec<String> = lines[..end].iter().map(|l| l.to_string()).collect();
+    result.push(String::new());
+    result.push(format!("Refs #{}", number));
+    result.extend(lines[end..].iter().map(|l| l.to_string()));
+    result.join("\n") + "\n"
+}
+
+/// Errors out if one of the issues doesn't exist. The local cache is looked at first and the
+/// issues found through the API are added to it, references can't be checked without network
+/// and are accepted then. Only a 404 from a project the token can see means the issue is missing.
+fn check_issues_exist(http: &mut HttpClient, project: &Project, numbers: &[u64]) -> Result<(), GliError> {
+    let cached: Vec<u64> = cache::load(project)
+        .map(|c| c.issues.iter().map(|i| i.number).collect())
+        .unwrap_or_default();
+    let unknown: Vec<u64> = numbers.iter().cloned().filter(|n| !cached.contains(n)).collect();
+    let mut verified = vec![];
+    for number in unknown {
+        match forge::get_issue(http, project, number) {
+            Ok(issue) => verified.push(issue),
+            Err(GliError::NotFound(_)) if forge::project_exists(http, project)? => {
+                return Err(GliError::NotFound(format!("issue #{} in {}", number, project.name())))
+            }
+            Err(GliError::NotFound(_)) => {
+                return Err(GliError::Other(format!(
+                    "{} can't be found, check the origin remote and that your token can see the project",
+                    project.name()
+                )))
+            }
+            Err(GliError::Network(e)) | Err(GliError::Timeout(e)) => {
+                eprintln!("gli: couldn't check that #{} exists ({})", number, e);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if !verified.is_empty() {
+        cache::add_issues(project, verified)?;
+    }
+    Ok(())
+}
+
+/// Adds a reference to the issue of the branch if the message has none, then checks the references
+fn check_message(path: &str, message: &str, config: &Config) -> Result<String, GliError> {
+    let project = extract_project(config)?;
+    let mut http = HttpClient::new(&project.account)?;
+    let numbers = issue_references(message);
+    if numbers.is_empty() {
+        let inferred = match branch::verified_issue(&mut http, &project, config) {
+            Ok(inferred) => inferred,
+            Err(GliError::Network(_)) | Err(GliError::Timeout(_)) => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(number) = inferred {
+            File::create(path)?.write_all(append_reference(message, number).as_bytes())?;
+            return Ok(format!("gli: added Refs #{}", number));
+        }
+    }
+    if numbers.is_empty() {
+        return match config.commit_policy() {
+            CommitPolicy::Allow => Ok(String::new()),
+            CommitPolicy::Warn => Ok("gli: this commit doesn't reference any issue".to_owned()),
+            CommitPolicy::Require => Err(GliError::Config(
+                "Commits must reference an issue, eg `Refs #12` (commit_policy is require)".to_owned(),
+            )),
+        };
+    }
+    check_issues_exist(&mut http, &project, &numbers)?;
+    Ok(String::new())
+}
+
+/// Run by the hook with the path of the commit message. Unless `commit_policy` is `require`,
+/// only references to missing issues block the commit: other failures (no configuration,
+/// invalid token, rate limit, …) are reported as warnings.
+pub fn commit_msg(path: &str) -> Result<String, GliError> {
+    let mut message = String::new();
+    File::open(path)?.read_to_string(&mut message)?;
+    if message.starts_with("Merge ") || message.starts_with("fixup!") || message.starts_with("squash!") {
+        return Ok(String::new());
+    }
+
+    let config = match read_config() {
+        Ok(config) => config,
+        Err(e) => return Ok(format!("gli: couldn't check the issue references ({})", e)),
+    };
+    match check_message(path, &message, &config) {
+        Ok(report) => Ok(report),
+        Err(e @ GliError::NotFound(_)) => Err(e),
+        Err(e) => match config.commit_policy() {
+            CommitPolicy::Require => Err(e),
+            _ => Ok(format!("gli: couldn't check the issue references ({})", e)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod reference_tests {
+    use super::*;
+
+    #[test]
+    fn finding_references() {
+        assert_eq!(issue_references("Fix crash, closes #12 and #3\n\nSee #12"), vec![12, 3]);
+        assert_eq!(issue_references("Use color#fff\n# Comment #4"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn appending_references() {
+        assert_eq!(append_reference("Fix crash\n", 12), "Fix crash\n\nRefs #12\n");
+        assert_eq!(
+            append_reference("Fix crash\n\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff", 12),
+            "Fix crash\n\nRefs #12\n\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff\n"
+        );
+    }
+}
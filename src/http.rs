@@ -0,0 +1,366 @@
+use config::Account;
+use errors::GliError;
+use futures::{Future, Stream};
+use hyper::{Chunk, Client, Method, Request, StatusCode};
+use hyper::client::HttpConnector;
+use hyper::header::{Basic, ContentType, Headers, UserAgent};
+use hyper_proxy::{Intercept, Proxy, ProxyConnector};
+use hyper_tls::HttpsConnector;
+use native_tls::{Certificate, Pkcs12, TlsConnector};
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_core::reactor::{Core, Timeout};
+use trace;
+use url::Url;
+
+const USER_AGENT: &'static str = concat!("gli/", env!("CARGO_PKG_VERSION"));
+const TIMEOUT_SECS: u64 = 30;
+const MAX_RETRIES: u32 = 3;
+/// Don't wait longer than that for a quota reset, fail with `RateLimited` instead
+const MAX_WAIT_SECS: u64 = 60;
+
+pub struct Response {
+    pub status:  StatusCode,
+    pub headers: Headers,
+    pub body:    Chunk,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get_raw(name)
+            .and_then(|r| r.one())
+            .and_then(|v| str::from_utf8(v).ok())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, GliError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// HTTP client shared by every API call of a command, so connections
+/// (and TLS sessions) are reused between calls.
+/// Proxy and TLS settings come from the account of the current project.
+pub struct HttpClient {
+    core:       Core,
+    client:     Client<ProxyConnector<HttpsConnector<HttpConnector>>>,
+    timeout:    Duration,
+    rate_limit: Option<RateLimit>,
+}
+
+impl HttpClient {
+    pub fn new(account: &Account) -> Result<HttpClient, GliError> {
+        let core = Core::new()?;
+        let tls = tls_connector(account)?;
+        let mut http = HttpConnector::new(4, &core.handle());
+        http.enforce_http(false);
+        let mut https = HttpsConnector::from((http, tls.clone()));
+        https.danger_disable_hostname_verification(account.skip_hostname_verification);
+        let mut connector = ProxyConnector::new(https)?;
+        connector.set_tls(Some(tls));
+        if let Some(proxy) = proxy_for(account)? {
+            // The TLS connection tunneled through the proxy always checks the hostname
+            if account.skip_hostname_verification {
+                return Err(GliError::Config(format!(
+                    "skip_hostname_verification is not supported through a proxy, add {} to no_proxy or remove the setting",
+                    account.domain
+                )));
+            }
+            connector.add_proxy(proxy);
+        }
+        let client = Client::configure()
+            .connector(connector)
+            .keep_alive(true)
+            .build(&core.handle());
+
+        Ok(HttpClient {
+            core,
+            client,
+            timeout: Duration::from_secs(TIMEOUT_SECS),
+            rate_limit: None,
+        })
+    }
+
+    /// Sends a request and buffers the whole response.
+    /// Non-2xx statuses are not errors at this level, each API module decodes its own error payloads,
+    /// except rate limiting: idempotent requests are retried with an exponential backoff on 429 and 5xx,
+    /// and a `RateLimited` error is returned once retries are exhausted.
+    /// Bodies are sent as JSON unless `headers` have a `Content-Type`.
+    pub fn send(&mut self, method: Method, url: &str, headers: Headers, body: Option<Vec<u8>>) -> Result<Response, GliError> {
+        let idempotent = match method {
+            Method::Get | Method::Head | Method::Put | Method::Delete | Method::Options => true,
+            _ => false,
+        };
+        let mut attempt = 0;
+        loop {
+            let res = self.send_once(method.clone(), url, &headers, &body)?;
+            let rate_limit = RateLimit::from_response(&res);
+            if rate_limit.remaining.is_some() {
+                self.rate_limit = Some(rate_limit.clone());
+            }
+            let limited = res.status.as_u16() == 429 || (res.status.as_u16() == 403 && rate_limit.remaining == Some(0));
+            let retryable = limited || res.status.is_server_error();
+            if !retryable {
+                return Ok(res);
+            }
+
+            // Github sends its rate limit reset time with every response, it only matters when limited.
+            // A reset time already passed would retry at once, the backoff is the minimum wait.
+            let backoff = 1 << attempt;
+            let wait = if limited {
+                retry_after(&res)
+                    .or_else(|| rate_limit.reset_in())
+                    .map(|w| w.max(backoff))
+                    .unwrap_or(backoff)
+            } else {
+                backoff
+            };
+            if !idempotent || attempt >= MAX_RETRIES || wait > MAX_WAIT_SECS {
+                return if limited {
+                    Err(GliError::RateLimited(Some(wait)))
+                } else {
+                    Ok(res)
+                };
+            }
+            eprintln!(
+                "{} {} answered {}, retrying in {} seconds",
+                method,
+                url,
+                res.status.as_u16(),
+                wait
+            );
+            thread::sleep(Duration::from_secs(wait));
+            attempt += 1;
+        }
+    }
+
+    /// Quota reported by the last response that had rate limit headers
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    fn send_once(&mut self, method: Method, url: &str, headers: &Headers, body: &Option<Vec<u8>>) -> Result<Response, GliError> {
+        let uri = url.parse()?;
+        let mut request = Request::new(method.clone(), uri);
+        request.headers_mut().extend(headers.iter());
+        request.headers_mut().set(UserAgent::new(USER_AGENT));
+        if let &Some(ref b) = body {
+            if request.headers().get_raw("Content-Type").is_none() {
+                request.headers_mut().set(ContentType::json());
+            }
+            request.set_body(b.clone());
+        }
+        trace::log(trace::INFO, &format!("--> {} {}", method, url));
+        if trace::enabled(trace::DEBUG) {
+            trace::log(trace::DEBUG, &trace::format_headers(request.headers()));
+            if let &Some(ref b) = body {
+                match str::from_utf8(b) {
+                    Ok(text) => trace::log(trace::DEBUG, text),
+                    Err(_) => trace::log(trace::DEBUG, &format!("[{} bytes]", b.len())),
+                }
+            }
+        }
+        let start = Instant::now();
+
+        let work = self.client
+            .request(request)
+            .and_then(|res| {
+                let status = res.status();
+                let headers = res.headers().clone();
+                res.body().concat2().map(move |body| Response { status, headers, body })
+            })
+            .map_err(GliError::from);
+        let timeout_secs = self.timeout.as_secs();
+        let timeout = Timeout::new(self.timeout, &self.core.handle())?.then(move |_| {
+            Err(GliError::Timeout(format!(
+                "no response after {} seconds",
+                timeout_secs
+            )))
+        });
+
+        let result = self.core
+            .run(work.select(timeout))
+            .map(|(res, _)| res)
+            .map_err(|(e, _)| e);
+
+        let elapsed = start.elapsed();
+        let ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000);
+        match result {
+            Ok(ref res) => {
+                trace::log(
+                    trace::INFO,
+                    &format!("<-- {} {} {} ({} ms)", res.status, method, url, ms),
+                );
+                if trace::enabled(trace::DEBUG) {
+                    trace::log(trace::DEBUG, &trace::format_headers(&res.headers));
+                    trace::log(trace::DEBUG, &String::from_utf8_lossy(&res.body));
+                }
+            }
+            Err(ref e) => trace::log(
+                trace::INFO,
+                &format!("<-- {} {} failed after {} ms: {}", method, url, ms, e),
+            ),
+        }
+        result
+    }
+}
+
+/// Github sends `X-RateLimit-*` headers, gitlab `RateLimit-*` ones.
+/// In both cases `reset` is a unix timestamp.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit {
+    pub limit:     Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset:     Option<u64>,
+}
+
+impl RateLimit {
+    fn from_response(res: &Response) -> RateLimit {
+        let get = |name: &str| {
+            res.header(&format!("X-{}", name))
+                .or_else(|| res.header(name))
+                .and_then(|v| v.trim().parse().ok())
+        };
+        RateLimit {
+            limit:     get("RateLimit-Limit"),
+            remaining: get("RateLimit-Remaining"),
+            reset:     get("RateLimit-Reset"),
+        }
+    }
+
+    /// Seconds until the quota is reset
+    pub fn reset_in(&self) -> Option<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.reset.map(|r| if r > now { r - now } else { 0 })
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.remaining, self.limit) {
+            (Some(r), Some(l)) => write!(f, "{}/{} requests remaining", r, l)?,
+            (Some(r), None) => write!(f, "{} requests remaining", r)?,
+            _ => write!(f, "no rate limit information")?,
+        }
+        match self.reset_in() {
+            Some(s) => write!(f, ", reset in {} minutes", (s + 59) / 60),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `Retry-After` can be a number of seconds or an HTTP date, only the former is used by forges
+fn retry_after(res: &Response) -> Option<u64> {
+    res.header("Retry-After").and_then(|v| v.trim().parse().ok())
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, GliError> {
+    let mut contents = vec![];
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut contents))
+        .map_err(|e| GliError::Config(format!("can't read {}: {}", path, e)))?;
+    Ok(contents)
+}
+
+fn tls_error(path: &str, e: ::native_tls::Error) -> GliError {
+    GliError::Config(format!("can't use {}: {}", path, e))
+}
+
+/// Adds the account's CA bundle and client certificate to the system defaults
+fn tls_connector(account: &Account) -> Result<TlsConnector, GliError> {
+    let mut builder = TlsConnector::builder()?;
+    if let Some(ref path) = account.ca_file {
+        let bundle = String::from_utf8_lossy(&read_file(path)?).into_owned();
+        let end = "-----END CERTIFICATE-----";
+        for pem in bundle.split(end).filter(|p| p.contains("-----BEGIN CERTIFICATE-----")) {
+            let cert = Certificate::from_pem(format!("{}{}\n", pem.trim_left(), end).as_bytes()).map_err(|e| tls_error(path, e))?;
+            builder.add_root_certificate(cert).map_err(|e| tls_error(path, e))?;
+        }
+    }
+    if let Some(ref path) = account.client_cert {
+        let password = account
+            .client_cert_password
+            .as_ref()
+            .map(|p| p.as_str())
+            .unwrap_or("");
+        let identity = Pkcs12::from_der(&read_file(path)?, password).map_err(|e| tls_error(path, e))?;
+        builder.identity(identity).map_err(|e| tls_error(path, e))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// `no_proxy` is a comma separated list of domains, `*` disables the proxy for everything
+fn bypasses_proxy(no_proxy: &str, domain: &str) -> bool {
+    no_proxy
+        .split(',')
+        .map(|d| d.trim().trim_left_matches('.'))
+        .filter(|d| !d.is_empty())
+        .any(|d| d == "*" || domain == d || domain.ends_with(&format!(".{}", d)))
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name.to_lowercase())
+        .or_else(|_| env::var(name.to_uppercase()))
+        .ok()
+        .and_then(|v| if v.is_empty() { None } else { Some(v) })
+}
+
+/// Account settings take precedence over the `https_proxy` and `no_proxy` environment variables
+fn proxy_for(account: &Account) -> Result<Option<Proxy>, GliError> {
+    let domain = if account.forge == ::config::Forge::Github {
+        "api.github.com"
+    } else {
+        &account.domain
+    };
+    let no_proxy = account.no_proxy.clone().or_else(|| env_var("no_proxy"));
+    if no_proxy.map(|n| bypasses_proxy(&n, domain)).unwrap_or(false) {
+        return Ok(None);
+    }
+    let raw = match account.https_proxy.clone().or_else(|| env_var("https_proxy")) {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+    let invalid = || GliError::Config(format!("invalid proxy url `{}`", raw));
+    let mut url = Url::parse(&raw).map_err(|_| invalid())?;
+    let credentials = if url.username().is_empty() {
+        None
+    } else {
+        Some(Basic {
+            username: url.username().to_owned(),
+            password: url.password().map(|p| p.to_owned()),
+        })
+    };
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    let mut proxy = Proxy::new(Intercept::All, url.as_str().parse().map_err(|_| invalid())?);
+    if let Some(c) = credentials {
+        proxy.set_authorization(c);
+    }
+    Ok(Some(proxy))
+}
+
+#[cfg(test)]
+mod proxy_tests {
+    use super::*;
+
+    #[test]
+    fn matching_no_proxy() {
+        assert!(bypasses_proxy("localhost,gitlab.example.org", "gitlab.example.org"));
+        assert!(bypasses_proxy(".example.org", "gitlab.example.org"));
+        assert!(bypasses_proxy("example.org", "gitlab.example.org"));
+        assert!(bypasses_proxy("*", "github.com"));
+        assert!(!bypasses_proxy("example.org", "gitlab.example.com"));
+        assert!(!bypasses_proxy("ample.org", "gitlab.example.org"));
+        assert!(!bypasses_proxy("", "github.com"));
+    }
+}
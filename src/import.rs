@@ -0,0 +1,315 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use serde_json;
+use serde_yaml;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::mem;
+
+const CHECKLIST_ITEMS: &'static [&'static str] = &["- [ ] ", "* [ ] ", "+ [ ] "];
+
+/// A milestone is given by its id (its number on github) or its title
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Milestone {
+    Id(u64),
+    Title(String),
+}
+
+/// One issue to create, as read from the file
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Row {
+    pub title:     String,
+    pub body:      Option<String>,
+    pub labels:    Vec<String>,
+    pub assignee:  Option<String>,
+    pub milestone: Option<Milestone>,
+}
+
+/// Splits CSV content into records. Quoted fields can contain commas, newlines and doubled quotes.
+pub fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, GliError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                field.push(c);
+            } else if chars.peek() == Some(&'"') {
+                field.push('"');
+                chars.next();
+            } else {
+                quoted = false;
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => record.push(mem::replace(&mut field, String::new())),
+            '\r' => {}
+            '\n' => {
+                record.push(mem::replace(&mut field, String::new()));
+                records.push(mem::replace(&mut record, vec![]));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(GliError::Other("The CSV file ends in the middle of a quoted field".to_owned()));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    Ok(records)
+}
+
+/// Rows of a CSV file with a header naming the columns: `title`, `body`, `labels` (comma separated),
+/// `assignee` and `milestone`
+pub fn rows_from_csv(content: &str) -> Result<Vec<Row>, GliError> {
+    let mut records = parse_csv(content)?.into_iter();
+    let header: Vec<String> = match records.next() {
+        Some(header) => header.iter().map(|h| h.trim().to_lowercase()).collect(),
+        None => return Ok(vec![]),
+    };
+    if let Some(unknown) = header
+        .iter()
+        .find(|h| !["title", "body", "labels", "assignee", "milestone"].contains(&h.as_str()))
+    {
+        return Err(GliError::Other(format!(
+            "Unknown CSV column `{}`, expected title, body, labels, assignee and milestone",
+            unknown
+        )));
+    }
+    let mut rows = vec![];
+    for (i, record) in records.enumerate() {
+        let mut row = Row::default();
+        for (column, value) in header.iter().zip(record.into_iter()) {
+            let value = value.trim().to_owned();
+            if value.is_empty() {
+                continue;
+            }
+            match column.as_str() {
+                "title" => row.title = value,
+                "body" => row.body = Some(value),
+                "labels" => {
+                    row.labels = value
+                        .split(',')
+                        .map(|l| l.trim().to_owned())
+                        .filter(|l| !l.is_empty())
+                        .collect()
+                }
+                "assignee" => row.assignee = Some(value),
+                _ => {
+                    row.milestone = Some(match value.parse() {
+                        Ok(id) => Milestone::Id(id),
+                        Err(_) => Milestone::Title(value),
+                    })
+                }
+            }
+        }
+        if row.title.is_empty() {
+            return Err(GliError::Other(format!("Row {} of the CSV file has no title", i + 2)));
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Unchecked items of a Markdown checklist (`- [ ] title`), the indented lines below an item are its body
+pub fn rows_from_checklist(content: &str) -> Vec<Row> {
+    let mut rows: Vec<Row> = vec![];
+    let mut in_item = false;
+    for line in content.lines() {
+        let trimmed = line.trim_left();
+        if let Some(prefix) = CHECKLIST_ITEMS.iter().find(|p| trimmed.starts_with(*p)) {
+            rows.push(Row {
+                title: trimmed[prefix.len()..].trim().to_owned(),
+                ..Row::default()
+            });
+            in_item = true;
+        } else if trimmed.is_empty() {
+            continue;
+        } else if in_item && trimmed.len() < line.len() && !trimmed.starts_with("- [") && !trimmed.starts_with("* [") {
+            let row = rows.last_mut().unwrap();
+            let body = match row.body.take() {
+                Some(body) => format!("{}\n{}", body, trimmed),
+                None => trimmed.to_owned(),
+            };
+            row.body = Some(body);
+        } else {
+            in_item = false;
+        }
+    }
+    rows.retain(|r| !r.title.is_empty());
+    rows
+}
+
+/// Reads the issues of a `.csv`, `.yml`/`.yaml`, `.json` or `.md` file
+pub fn read(path: &str) -> Result<Vec<Row>, GliError> {
+    let mut content = String::new();
+    File::open(path)
+        .map_err(|_| GliError::NotFound(format!("file {}", path)))?
+        .read_to_string(&mut content)?;
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    let invalid = |e: String| GliError::Other(format!("Couldn't read {}: {}", path, e));
+    match extension.as_str() {
+        "csv" => rows_from_csv(&content),
+        "yml" | "yaml" => serde_yaml::from_str(&content).map_err(|e| invalid(e.to_string())),
+        "json" => serde_json::from_str(&content).map_err(|e| invalid(e.to_string())),
+        "md" | "markdown" => Ok(rows_from_checklist(&content)),
+        _ => Err(GliError::Other(format!(
+            "Don't know how to read {}, use a .csv, .yml, .json or .md file",
+            path
+        ))),
+    }
+}
+
+fn labels_of(row: &Row, labels: &[String]) -> Vec<String> {
+    let mut all = row.labels.clone();
+    all.extend(labels.iter().filter(|l| !row.labels.contains(l)).cloned());
+    all
+}
+
+/// Every label used by the import, once
+pub fn all_labels(rows: &[Row], labels: &[String]) -> Vec<String> {
+    let mut all = labels.to_vec();
+    for label in rows.iter().flat_map(|r| r.labels.iter()) {
+        if !all.contains(label) {
+            all.push(label.clone());
+        }
+    }
+    all
+}
+
+/// What would be created, one issue per line
+pub fn preview(rows: &[Row], labels: &[String]) -> String {
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut line = format!("{}. {}", i + 1, row.title);
+            let labels = labels_of(row, labels);
+            if !labels.is_empty() {
+                line.push_str(&format!(" [{}]", labels.join(", ")));
+            }
+            if let Some(ref assignee) = row.assignee {
+                line.push_str(&format!(" @{}", assignee));
+            }
+            match row.milestone {
+                Some(Milestone::Id(id)) => line.push_str(&format!(" (milestone {})", id)),
+                Some(Milestone::Title(ref title)) => line.push_str(&format!(" (milestone {})", title)),
+                None => {}
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn new_issue(
+    http: &mut HttpClient,
+    project: &Project,
+    row: &Row,
+    labels: &[String],
+    milestones: &mut HashMap<String, u64>,
+) -> Result<NewIssue, GliError> {
+    let milestone_id = match row.milestone {
+        Some(Milestone::Id(id)) => Some(id),
+        Some(Milestone::Title(ref title)) => {
+            if !milestones.contains_key(title) {
+                let id = forge::milestone_id(http, project, title)?;
+                milestones.insert(title.clone(), id);
+            }
+            milestones.get(title).cloned()
+        }
+        None => None,
+    };
+    Ok(NewIssue {
+        title: row.title.clone(),
+        text: row.body.clone(),
+        labels: labels_of(row, labels),
+        assignee: row.assignee.clone(),
+        milestone_id: milestone_id,
+        ..NewIssue::default()
+    })
+}
+
+/// Creates the issues in order. A failing row doesn't stop the import, except for network errors
+/// since the following rows would fail too, and timeouts since the row may have been created.
+pub fn create(http: &mut HttpClient, project: &Project, rows: &[Row], labels: &[String]) -> Result<String, GliError> {
+    let mut milestones = HashMap::new();
+    let mut report = vec![];
+    let mut created = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let result = new_issue(http, project, row, labels, &mut milestones)
+            .and_then(|issue| forge::create_issue(http, project, &issue));
+        match result {
+            Ok((number, url)) => {
+                created += 1;
+                report.push(format!("Created issue #{} {} {}", number, row.title, url));
+            }
+            Err(GliError::Network(e)) => {
+                report.push(format!(
+                    "Couldn't reach {} ({}), stopped before {}. {}",
+                    project.account.domain,
+                    e,
+                    i + 1,
+                    row.title
+                ));
+                break;
+            }
+            Err(GliError::Timeout(e)) => {
+                report.push(format!(
+                    "No answer from {} ({}), {}. {} may have been created, check before importing the remaining rows",
+                    project.account.domain,
+                    e,
+                    i + 1,
+                    row.title
+                ));
+                break;
+            }
+            Err(e) => report.push(format!("Couldn't create {}. {}: {}", i + 1, row.title, e)),
+        }
+    }
+    report.push(format!("{} of {} issue(s) created", created, rows.len()));
+    Ok(report.join("\n"))
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::*;
+
+    #[test]
+    fn parsing_csv() {
+        assert_eq!(
+            parse_csv("title,labels\n\"Fix \"\"login\"\"\",\"bug, ui\"\r\n\nAdd docs,\n").unwrap(),
+            vec![
+                vec!["title".to_owned(), "labels".to_owned()],
+                vec!["Fix \"login\"".to_owned(), "bug, ui".to_owned()],
+                vec!["Add docs".to_owned(), "".to_owned()],
+            ]
+        );
+        let rows = rows_from_csv("Title,Labels,Milestone\nFix login,\"bug, ui\",3\nAdd docs,,Sprint 4").unwrap();
+        assert_eq!(rows[0].labels, vec!["bug".to_owned(), "ui".to_owned()]);
+        assert_eq!(rows[0].milestone, Some(Milestone::Id(3)));
+        assert_eq!(rows[1].milestone, Some(Milestone::Title("Sprint 4".to_owned())));
+        assert!(rows_from_csv("name\nFix login").is_err());
+    }
+
+    #[test]
+    fn reading_checklists() {
+        let rows = rows_from_checklist("# Sprint 4\n\n- [ ] Fix login\n  It fails with SSO\n- [x] Done already\n  ignored\n* [ ] Add docs\nNotes");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].title, "Fix login");
+        assert_eq!(rows[0].body, Some("It fails with SSO".to_owned()));
+        assert_eq!(rows[1].title, "Add docs");
+        assert_eq!(rows[1].body, None);
+    }
+}
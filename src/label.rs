@@ -0,0 +1,158 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+
+/// Color of the labels created without `--color`, the one github uses
+pub const DEFAULT_COLOR: &'static str = "#ededed";
+
+/// `A1B2C3` and `#a1b2c3` both become `#a1b2c3`
+pub fn normalize_color(color: &str) -> Result<String, GliError> {
+    let hex = color.trim().trim_left_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_digit(16)) {
+        return Err(GliError::Other(format!("Invalid color `{}`, expected eg #d9534f", color)));
+    }
+    Ok(format!("#{}", hex.to_lowercase()))
+}
+
+/// Levenshtein distance, case insensitive
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + if a[i] == b[j] { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The existing label closest to `name`, if it's close enough to be a typo
+pub fn suggestion<'a>(name: &str, labels: &'a [Label]) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(2);
+    labels
+        .iter()
+        .map(|l| (distance(name, &l.name), l.name.as_str()))
+        .filter(|&(d, _)| d <= max)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, n)| n)
+}
+
+/// Errors out on labels the project doesn't have, suggesting the closest existing one.
+/// Names are compared ignoring case, as github does. Labels can't be checked without network,
+/// they're accepted then.
+pub fn check(http: &mut HttpClient, project: &Project, names: &[String]) -> Result<(), GliError> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let labels = match forge::fetch_labels(http, project) {
+        Ok(labels) => labels,
+        Err(GliError::Network(_)) | Err(GliError::Timeout(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    match unknown(names, &labels) {
+        Some(name) => Err(GliError::Other(match suggestion(name, &labels) {
+            Some(existing) => format!("Unknown label `{}`, did you mean `{}`?", name, existing),
+            None => format!(
+                "Unknown label `{}`, create it with `gli label create \"{}\"` or use --no-label-check",
+                name, name
+            ),
+        })),
+        None => Ok(()),
+    }
+}
+
+/// The first of `names` which isn't one of `labels`
+fn unknown<'a>(names: &'a [String], labels: &[Label]) -> Option<&'a String> {
+    names
+        .iter()
+        .find(|name| !labels.iter().any(|l| l.name.to_lowercase() == name.to_lowercase()))
+}
+
+/// One label per line: name, color and description
+pub fn list(labels: &[Label]) -> String {
+    if labels.is_empty() {
+        return "This project has no labels".to_owned();
+    }
+    let width = labels.iter().map(|l| l.name.chars().count()).max().unwrap_or(0);
+    labels
+        .iter()
+        .map(|l| {
+            let padding = " ".repeat(width - l.name.chars().count());
+            format!("{}{}  {}  {}", l.name, padding, l.color, l.description.clone().unwrap_or_default())
+                .trim_right()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Creates the labels of `source` missing from `project`, and updates the color and description of the others
+pub fn sync(http: &mut HttpClient, project: &Project, source: &Project) -> Result<String, GliError> {
+    let mut source_http = HttpClient::new(&source.account)?;
+    let wanted = forge::fetch_labels(&mut source_http, source)?;
+    let existing = forge::fetch_labels(http, project)?;
+    let mut report = vec![];
+    for label in &wanted {
+        match existing.iter().find(|l| l.name.to_lowercase() == label.name.to_lowercase()) {
+            None => {
+                forge::create_label(http, project, label)?;
+                report.push(format!("Created {}", label.name));
+            }
+            Some(current) if current.color.to_lowercase() != label.color.to_lowercase() || current.description != label.description => {
+                let update = LabelUpdate {
+                    new_name:    if current.name != label.name { Some(label.name.clone()) } else { None },
+                    color:       Some(label.color.clone()),
+                    description: Some(label.description.clone().unwrap_or_default()),
+                };
+                forge::update_label(http, project, &current.name, &update)?;
+                report.push(format!("Updated {}", label.name));
+            }
+            Some(_) => {}
+        }
+    }
+    if report.is_empty() {
+        return Ok(format!("The labels of {} already match {}", project.name(), source.name()));
+    }
+    Ok(report.join("\n"))
+}
+
+#[cfg(test)]
+mod label_tests {
+    use super::*;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name:        name.to_owned(),
+            color:       DEFAULT_COLOR.to_owned(),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn suggesting_labels() {
+        let labels = vec![label("bug"), label("enhancement"), label("documentation")];
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(suggestion("bgu", &labels), Some("bug"));
+        assert_eq!(suggestion("Enhancment", &labels), Some("enhancement"));
+        assert_eq!(suggestion("security", &labels), None);
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let labels = vec![label("bug"), label("Enhancement")];
+        assert_eq!(unknown(&["Bug".to_owned(), "enhancement".to_owned()], &labels), None);
+        assert_eq!(unknown(&["bug".to_owned(), "bugs".to_owned()], &labels), Some(&"bugs".to_owned()));
+    }
+
+    #[test]
+    fn normalizing_colors() {
+        assert_eq!(normalize_color("D9534F").unwrap(), "#d9534f");
+        assert_eq!(normalize_color("#d9534f").unwrap(), "#d9534f");
+        assert!(normalize_color("red").is_err());
+    }
+}
@@ -1,83 +1,574 @@
+extern crate base64;
 extern crate futures;
 extern crate git2;
-extern crate github_rs as gh;
-extern crate gitlab;
 extern crate hyper;
+extern crate hyper_proxy;
 extern crate hyper_tls;
 extern crate itertools;
+extern crate native_tls;
 #[macro_use]
 extern crate nom;
 extern crate open;
+extern crate regex;
 extern crate rprompt;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate serde_yaml;
 extern crate structopt;
 #[macro_use]
 extern crate structopt_derive;
+extern crate termion;
 extern crate tokio_core;
 extern crate toml;
 extern crate url;
 extern crate xdg;
 
+mod attach;
+mod body;
+mod branch;
+mod browse;
+mod cache;
 mod config;
+mod context;
+mod duplicates;
+mod errors;
+mod export;
+mod forge;
+mod git;
 mod gitlab_api;
 mod github_api;
+mod hook;
+mod http;
+mod import;
+mod label;
+mod migrate;
+mod mr;
+mod picker;
+mod queue;
+mod todo;
+mod trace;
+mod tui;
 
+use browse::Page;
 use config::*;
-use std::error::Error;
+use duplicates::Decision;
+use errors::GliError;
+use export::ExportFormat;
+use http::HttpClient;
+use std::process;
 use structopt::StructOpt;
 
-fn do_work(cmd: &Cmd) -> Result<String, Box<Error>> {
+fn do_work(cmd: &Cmd) -> Result<String, GliError> {
     match cmd {
         &Cmd::OpenIssue {
             open_browser,
             ref labels,
             ref assignee,
+            confidential,
+            ref due_date,
+            weight,
+            milestone_id,
+            epic_id,
+            ref issue_type,
+            no_dup_check,
+            no_label_check,
+            ref attachments,
+            ref body_file,
+            with_context,
             ref title,
             ref text,
         } => {
             let config = read_config()?;
             let project = extract_project(&config)?;
-            match &project.place {
-                &Place::Gitlab(_) => {
-                    let res = gitlab_api::create_issue(&config, &project, title, text, labels, assignee)?;
-                    let url = gitlab_api::get_issue_url(&config.gitlab_domain, &project.name(), &res);
-                    if open_browser {
-                        gitlab_api::open_gitlab(&config.gitlab_domain, &project, Some(res))?
+            let mut http = HttpClient::new(&project.account)?;
+            let context = if with_context { Some(context::gather(&config)?) } else { None };
+            // Leaves room for the context and the attachment links
+            let max = forge::max_body_length(&project)
+                .saturating_sub(context.as_ref().map(|c| c.chars().count() + 2).unwrap_or(0))
+                .saturating_sub(attachments.len() * 200);
+            let text = match (body::read(text.clone(), body_file.as_ref().map(|f| f.as_str()), max)?, context) {
+                (Some(text), Some(context)) => Some(format!("{}\n\n{}", text, context)),
+                (text, context) => text.or(context),
+            };
+            let attachments = attachments
+                .iter()
+                .map(|path| attach::resolve(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let issue = NewIssue {
+                title:        title.clone(),
+                text:         text,
+                labels:       labels.clone(),
+                assignee:     assignee.clone(),
+                confidential: confidential,
+                due_date:     due_date.clone(),
+                weight:       weight,
+                milestone_id: milestone_id,
+                epic_id:      epic_id,
+                issue_type:   issue_type.clone(),
+                attachments:  attachments,
+            };
+            if !no_label_check {
+                label::check(&mut http, &project, &issue.labels)?;
+            }
+            if !no_dup_check {
+                match duplicates::check(&mut http, &project, &issue)? {
+                    Decision::Create => {}
+                    Decision::Comment(number) => {
+                        let body = match issue.text {
+                            Some(ref text) => format!("**{}**\n\n{}", issue.title, text),
+                            None => issue.title.clone(),
+                        };
+                        let body = attach::with_attachments(&mut http, &project, Some(body), &issue.attachments)?.unwrap_or_default();
+                        let url = forge::comment_issue(&mut http, &project, number, &body)?;
+                        return Ok(format!("Commented on issue #{} {}", number, url));
                     }
-                    Ok(format!("Created issue #{} {}", res, url))
+                    Decision::Abort => return Ok("No issue created".to_owned()),
                 }
-                &Place::Github => {
-                    let (number, url) = github_api::create_issue(&config, &project, title, text, labels, assignee)?;
+            }
+            match forge::create_issue(&mut http, &project, &issue) {
+                Ok((number, url)) => {
                     if open_browser {
                         open::that(&url)?;
                     }
                     Ok(format!("Created issue #{} {}", number, url))
                 }
+                Err(GliError::Network(e)) => {
+                    let id = queue::enqueue(&project, &issue)?;
+                    Ok(format!(
+                        "Couldn't reach {} ({}), the issue has been queued as {}.\nRun `gli sync` once you're back online.",
+                        &project.account.domain, e, id
+                    ))
+                }
+                Err(e) => Err(e),
             }
         }
-        &Cmd::Browse {} => {
+        &Cmd::Browse {
+            pick_issue,
+            mr,
+            branch,
+            commit,
+            print,
+            ref target,
+        } => {
             let config = read_config()?;
             let project = extract_project(&config)?;
-            match &project.place {
-                &Place::Gitlab(_) => {
-                    let _ = gitlab_api::open_gitlab(&config.gitlab_domain, &project, None);
+            let page = if let Some(number) = mr {
+                Page::MergeRequest(number)
+            } else if branch {
+                Page::Branch(git::current_branch()?)
+            } else if commit {
+                Page::Commit(git::commit_sha(target.as_ref().map(|t| t.as_str()))?)
+            } else if pick_issue {
+                let mut http = HttpClient::new(&project.account)?;
+                Page::Issue(picker::pick_issue(&mut http, &project)?)
+            } else {
+                match target {
+                    &None => Page::Project,
+                    &Some(ref target) => match target.trim_left_matches('#').parse() {
+                        Ok(number) => Page::Issue(number),
+                        Err(_) => browse::file_page(target)?,
+                    },
                 }
-                &Place::Github => {
-                    let _ = github_api::open_project(&project);
+            };
+            let url = browse::page_url(&project, &page);
+            if print {
+                Ok(url)
+            } else {
+                open::that(&url)?;
+                Ok(format!("Opening {}", url))
+            }
+        }
+        &Cmd::Show { issue } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            let number = picker::issue_or_pick(&mut http, &project, issue.or_else(|| branch::current_issue(&config)))?;
+            let issue = forge::get_issue(&mut http, &project, number)?;
+            let comments = forge::fetch_comments(&mut http, &project, number)?;
+            Ok(issue.details(&comments))
+        }
+        &Cmd::Comment {
+            ref message,
+            ref attachments,
+            issue,
+        } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            let number = picker::issue_or_pick(&mut http, &project, issue.or_else(|| branch::current_issue(&config)))?;
+            let body = match message {
+                &Some(ref message) => message.clone(),
+                &None => rprompt::prompt_reply_stdout(&format!("Comment on #{}: ", number))?,
+            };
+            if body.trim().is_empty() && attachments.is_empty() {
+                return Err("The comment can't be empty".into());
+            }
+            let body = attach::with_attachments(&mut http, &project, Some(body), attachments)?.unwrap_or_default();
+            let url = forge::comment_issue(&mut http, &project, number, &body)?;
+            Ok(format!("Commented on issue #{} {}", number, url))
+        }
+        &Cmd::Start {
+            assign,
+            in_progress,
+            issue,
+        } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            let number = picker::issue_or_pick(&mut http, &project, issue)?;
+            let issue = forge::get_issue(&mut http, &project, number)?;
+            let name = branch::branch_name(config.branch_pattern(), &issue);
+            git::create_branch(&name)?;
+            let mut report = vec![format!("Switched to a new branch {} for issue #{} {}", name, number, issue.title)];
+            if assign {
+                let user = forge::current_user(&mut http, &project)?;
+                forge::assign_issue(&mut http, &project, number, &user)?;
+                report.push(format!("Assigned #{} to {}", number, user));
+            }
+            if in_progress {
+                let label = config.in_progress_label().to_owned();
+                forge::add_labels(&mut http, &project, number, &[label.clone()])?;
+                report.push(format!("Labelled #{} {}", number, label));
+            }
+            Ok(report.join("\n"))
+        }
+        &Cmd::Export {
+            ref format,
+            ref since,
+            ref output,
+        } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            export::export(
+                &mut http,
+                &project,
+                since.as_ref().map(|s| s.as_str()),
+                format,
+                output.as_ref().map(|o| o.as_str()),
+            )
+        }
+        &Cmd::Migrate { ref from, ref to } => {
+            let config = read_config()?;
+            migrate::migrate(&config, from, to)
+        }
+        &Cmd::Label { ref action } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            match action {
+                &LabelAction::List {} => Ok(label::list(&forge::fetch_labels(&mut http, &project)?)),
+                &LabelAction::Create {
+                    ref color,
+                    ref description,
+                    ref name,
+                } => {
+                    let new_label = Label {
+                        name:        name.clone(),
+                        color:       label::normalize_color(color.as_ref().map(|c| c.as_str()).unwrap_or(label::DEFAULT_COLOR))?,
+                        description: description.clone(),
+                    };
+                    forge::create_label(&mut http, &project, &new_label)?;
+                    Ok(format!("Created label {}", name))
+                }
+                &LabelAction::Edit {
+                    ref new_name,
+                    ref color,
+                    ref description,
+                    ref name,
+                } => {
+                    if new_name.is_none() && color.is_none() && description.is_none() {
+                        return Err(GliError::Other(
+                            "Nothing to change, give --new-name, --color or --description".to_owned(),
+                        ));
+                    }
+                    let update = LabelUpdate {
+                        new_name:    new_name.clone(),
+                        color:       match color {
+                            &Some(ref c) => Some(label::normalize_color(c)?),
+                            &None => None,
+                        },
+                        description: description.clone(),
+                    };
+                    forge::update_label(&mut http, &project, name, &update)?;
+                    Ok(format!("Updated label {}", new_name.as_ref().unwrap_or(name)))
+                }
+                &LabelAction::Delete { yes, ref name } => {
+                    if !yes {
+                        let answer = rprompt::prompt_reply_stdout(&format!(
+                            "Delete label {} from {}? It will be removed from every issue. [y/N] ",
+                            name,
+                            project.name()
+                        ))?;
+                        if !["y", "yes"].contains(&answer.trim().to_lowercase().as_str()) {
+                            return Ok("No label deleted".to_owned());
+                        }
+                    }
+                    forge::delete_label(&mut http, &project, name)?;
+                    Ok(format!("Deleted label {}", name))
+                }
+                &LabelAction::Sync { ref from } => {
+                    let source = project_from_spec(&config, from)?;
+                    label::sync(&mut http, &project, &source)
                 }
             }
-            Ok(format!("Opening {}", &project.name()))
         }
-        &Cmd::ListIssues { ref filter_state } => {
+        &Cmd::Import {
+            dry_run,
+            yes,
+            no_label_check,
+            ref labels,
+            ref file,
+        } => {
+            let rows = import::read(file)?;
+            if rows.is_empty() {
+                return Ok(format!("No issues found in {}", file));
+            }
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            println!("{}", import::preview(&rows, labels));
+            if !no_label_check {
+                label::check(&mut http, &project, &import::all_labels(&rows, labels))?;
+            }
+            if dry_run {
+                return Ok(format!("Dry run, {} issue(s) would be created in {}", rows.len(), project.name()));
+            }
+            if !yes {
+                let answer = rprompt::prompt_reply_stdout(&format!("Create {} issue(s) in {}? [y/N] ", rows.len(), project.name()))?;
+                if !["y", "yes"].contains(&answer.trim().to_lowercase().as_str()) {
+                    return Ok("No issue created".to_owned());
+                }
+            }
+            import::create(&mut http, &project, &rows, labels)
+        }
+        &Cmd::Todo {
+            create,
+            rewrite,
+            yes,
+            ref labels,
+        } => {
+            let todos = todo::scan()?;
+            if !create {
+                return Ok(todo::list(&todos));
+            }
+            let pending = todo::pending(&todos);
+            if pending.is_empty() {
+                return Ok("Every comment already references an issue".to_owned());
+            }
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            println!("{}", todo::preview(&pending, labels));
+            if !yes {
+                let answer = rprompt::prompt_reply_stdout(&format!("Create {} issue(s) in {}? [y/N] ", pending.len(), project.name()))?;
+                if !["y", "yes"].contains(&answer.trim().to_lowercase().as_str()) {
+                    return Ok("No issue created".to_owned());
+                }
+            }
+            let mut http = HttpClient::new(&project.account)?;
+            todo::create(&mut http, &project, &pending, labels, rewrite)
+        }
+        &Cmd::Close { issue } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            let number = picker::issue_or_pick(&mut http, &project, issue)?;
+            forge::set_issue_state(&mut http, &project, number, &IssueFilter::Closed)?;
+            Ok(format!("Closed issue #{}", number))
+        }
+        &Cmd::ListIssues {
+            ref filter_state,
+            offline: true,
+        } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            Ok(cache::load_issues(&project)?
+                .iter()
+                .filter(|i| i.matches(filter_state))
+                .map(|i| i.summary())
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        &Cmd::ListIssues { ref filter_state, .. } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            match &project.place {
+                &Place::Gitlab(_) => gitlab_api::list_issues(&mut http, &project, filter_state),
+                &Place::Github => github_api::list_issues(&mut http, &project, filter_state),
+            }
+        }
+        &Cmd::Tui {} => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            tui::run(&mut http, &project)
+        }
+        &Cmd::Fetch { full } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
+            cache::fetch(&mut http, &project, full)
+        }
+        &Cmd::Search {
+            ref filter_state,
+            ref labels,
+            ref author,
+            offline,
+            ref query,
+        } => {
+            let config = read_config()?;
+            let project = extract_project(&config)?;
+            let query = IssueQuery {
+                text:   query.join(" "),
+                state:  filter_state.clone(),
+                labels: labels.clone(),
+                author: author.clone(),
+            };
+            let summaries: Vec<String> = if offline {
+                let issues = cache::load_issues(&project)?;
+                cache::search(&issues, &query.text)
+                    .into_iter()
+                    .filter(|i| query.matches(i))
+                    .map(|i| i.summary())
+                    .collect()
+            } else {
+                let mut http = HttpClient::new(&project.account)?;
+                forge::search_issues(&mut http, &project, &query)?
+                    .iter()
+                    .map(|i| i.summary())
+                    .collect()
+            };
+            Ok(summaries.join("\n"))
+        }
+        &Cmd::Status {} => {
             let config = read_config()?;
             let project = extract_project(&config)?;
+            let mut http = HttpClient::new(&project.account)?;
             match &project.place {
-                &Place::Gitlab(_) => gitlab_api::list_issues(config, &project, filter_state),
-                &Place::Github => github_api::list_issues(config, &project, filter_state),
+                &Place::Gitlab(_) => gitlab_api::status(&mut http, &project),
+                &Place::Github => github_api::status(&mut http, &project),
             }
         }
+        &Cmd::Queue { ref action } => match action {
+            &QueueAction::List {} => queue::list(),
+            &QueueAction::Edit { id } => queue::edit(id),
+            &QueueAction::Drop { id } => queue::remove(id),
+            &QueueAction::Sync {} => queue::sync(&read_config()?),
+        },
+        &Cmd::Sync {} => queue::sync(&read_config()?),
+        &Cmd::Mr { ref action } => match action {
+            &MrAction::Create {
+                ref target,
+                push,
+                ref title,
+                ref message,
+                ref labels,
+                ref assignee,
+                ref reviewers,
+                draft,
+                ref closes,
+            } => {
+                let config = read_config()?;
+                let project = extract_project(&config)?;
+                let mut http = HttpClient::new(&project.account)?;
+                let source = git::current_branch()?;
+                let target = match target {
+                    &Some(ref target) => target.clone(),
+                    &None => forge::default_branch(&mut http, &project)?,
+                };
+                if source == target {
+                    return Err(format!("You're on {}, create a branch for your changes first", target).into());
+                }
+                if push {
+                    git::push(&source)?;
+                }
+                let (default_title, default_body) = mr::prefill(&source, &git::commit_messages_since(&target)?);
+                let closes: Vec<u64> = if closes.is_empty() {
+                    branch::verified_issue(&mut http, &project, &config)?.into_iter().collect()
+                } else {
+                    closes.clone()
+                };
+                let request = NewMergeRequest {
+                    source_branch: source,
+                    target_branch: target,
+                    title:         title.clone().unwrap_or(default_title),
+                    body:          mr::with_closing_references(message.clone().or(default_body), &closes),
+                    labels:        labels.clone(),
+                    assignee:      assignee.clone(),
+                    reviewers:     reviewers.clone(),
+                    draft:         draft,
+                };
+                let (number, url) = forge::create_merge_request(&mut http, &project, &request)?;
+                Ok(format!("Created {} {}", mr::reference(&project, number), url))
+            }
+            &MrAction::List {
+                ref filter_state,
+                ref author,
+                ref reviewer,
+            } => {
+                let config = read_config()?;
+                let project = extract_project(&config)?;
+                let mut http = HttpClient::new(&project.account)?;
+                let mrs = forge::fetch_merge_requests(
+                    &mut http,
+                    &project,
+                    filter_state,
+                    author.as_ref().map(|a| a.as_str()),
+                    reviewer.as_ref().map(|r| r.as_str()),
+                )?;
+                Ok(mrs.iter()
+                    .map(|m| mr::summary(&project, m))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            &MrAction::Show { number } => {
+                let config = read_config()?;
+                let project = extract_project(&config)?;
+                let mut http = HttpClient::new(&project.account)?;
+                let request = forge::get_merge_request(&mut http, &project, number)?;
+                let status = forge::merge_request_status(&mut http, &project, number)?;
+                Ok(mr::details(&project, &request, &status))
+            }
+            &MrAction::Checkout { number, ref branch } => {
+                let config = read_config()?;
+                let project = extract_project(&config)?;
+                let mut http = HttpClient::new(&project.account)?;
+                let request = forge::get_merge_request(&mut http, &project, number)?;
+                let branch = branch.clone().unwrap_or(request.source_branch);
+                git::checkout_remote_ref(&mr::head_ref(&project, number), &branch)?;
+                Ok(format!(
+                    "Switched to branch {} ({})",
+                    branch,
+                    mr::reference(&project, number)
+                ))
+            }
+            &MrAction::Merge { number, squash, rebase } => {
+                let method = match (squash, rebase) {
+                    (true, true) => return Err("Choose between --squash and --rebase".into()),
+                    (true, false) => MergeMethod::Squash,
+                    (false, true) => MergeMethod::Rebase,
+                    (false, false) => MergeMethod::Merge,
+                };
+                let config = read_config()?;
+                let project = extract_project(&config)?;
+                let mut http = HttpClient::new(&project.account)?;
+                forge::merge_merge_request(&mut http, &project, number, &method)?;
+                Ok(format!("Merged {}", mr::reference(&project, number)))
+            }
+        },
+        &Cmd::Hook { ref action } => match action {
+            &HookAction::Install { force } => hook::install(force),
+            &HookAction::CommitMsg { ref file } => hook::commit_msg(file),
+        },
+        &Cmd::Config { ref action } => match action {
+            &ConfigAction::Get { ref key } => config_get(key),
+            &ConfigAction::Set { ref key, ref value } => config_set(key, value),
+            &ConfigAction::List {} => config_list(),
+            &ConfigAction::Validate {} => config_validate(),
+        },
         &Cmd::Init {} => {
             init_config()?;
             Ok(format!(
@@ -96,15 +587,104 @@ Happy hacking :-)"#
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "gl-helper", about = "Gitlab helper.")]
+struct Opt {
+    #[structopt(short = "v", long = "verbose", help = "Log API calls on stderr (-vv to include headers and bodies, also set by GLI_LOG=info|debug)")]
+    verbose: u64,
+    #[structopt(subcommand)] cmd: Cmd,
+}
+
+#[derive(StructOpt, Debug)]
 enum Cmd {
-    #[structopt(name = "b", about = "Open gitlab page in the browser")] Browse {},
+    #[structopt(name = "b", about = "Open a page of the project in the browser")]
+    Browse {
+        #[structopt(name = "issue", short = "i", long = "issue", help = "Pick the open issue to browse to")] pick_issue: bool,
+        #[structopt(name = "mr", long = "mr", help = "Merge request (pull request on github) to browse to")] mr: Option<u64>,
+        #[structopt(name = "branch", long = "branch", help = "Browse to the current branch")] branch: bool,
+        #[structopt(name = "commit", long = "commit", help = "Browse to a commit, the revision given as target or HEAD")] commit: bool,
+        #[structopt(name = "print", short = "p", long = "print", help = "Print the URL instead of opening the browser")] print: bool,
+        #[structopt(help = "Issue number, or file or directory with optional lines, eg src/main.rs:42-50")] target: Option<String>,
+    },
+    #[structopt(name = "show", about = "Show an issue and its comments")]
+    Show {
+        #[structopt(help = "Issue number, the one of the current branch or picked interactively when omitted")] issue: Option<u64>,
+    },
+    #[structopt(name = "comment", about = "Comment on an issue")]
+    Comment {
+        #[structopt(name = "message", short = "m", long = "message", help = "Text of the comment, asked for when omitted")] message: Option<String>,
+        #[structopt(name = "attach", long = "attach", help = "Upload a file and link it in the comment, can be repeated")] attachments: Vec<String>,
+        #[structopt(help = "Issue number, the one of the current branch or picked interactively when omitted")] issue: Option<u64>,
+    },
+    #[structopt(name = "export", about = "Dump every issue with its comments and events")]
+    Export {
+        #[structopt(name = "format", short = "f", long = "format", default_value = "jsonl",
+                    help = "Possible values are: jsonl (one JSON object per line), csv, markdown (one file per issue)")]
+        format: ExportFormat,
+        #[structopt(name = "since", long = "since", help = "Only export the issues updated after this date, eg 2018-01-31T00:00:00Z")]
+        since: Option<String>,
+        #[structopt(name = "output", short = "o", long = "output", help = "File to write, directory for markdown. Printed when omitted")]
+        output: Option<String>,
+    },
+    #[structopt(name = "migrate", about = "Copy the issues of a project to another one, eg from github to gitlab")]
+    Migrate {
+        #[structopt(name = "from", long = "from", help = "Project to copy, eg github:owner/repo")] from: String,
+        #[structopt(name = "to", long = "to", help = "Project to copy to, eg gitlab:group/project or gitlab.example.org:group/project")]
+        to: String,
+    },
+    #[structopt(name = "import", about = "Create issues from a CSV, YAML, JSON or Markdown checklist file")]
+    Import {
+        #[structopt(name = "dry-run", short = "n", long = "dry-run", help = "Only show the issues which would be created")] dry_run: bool,
+        #[structopt(name = "yes", short = "y", long = "yes", help = "Don't ask for confirmation")] yes: bool,
+        #[structopt(name = "no-label-check", long = "no-label-check", help = "Don't check that the labels exist in the project")]
+        no_label_check: bool,
+        #[structopt(name = "label", short = "l", long = "label", help = "Add labels to every issue")] labels: Vec<String>,
+        #[structopt(help = "File with the issues, its extension gives the format: .csv, .yml, .json or .md")] file: String,
+    },
+    #[structopt(name = "label", about = "Manage the labels of the project")]
+    Label {
+        #[structopt(subcommand)] action: LabelAction,
+    },
+    #[structopt(name = "todo", about = "List TODO, FIXME and XXX comments which don't reference an issue")]
+    Todo {
+        #[structopt(name = "create", short = "c", long = "create", help = "Open an issue for each of them")] create: bool,
+        #[structopt(name = "rewrite", short = "r", long = "rewrite", help = "Add the number of the new issue to the comment, eg TODO(#12)")]
+        rewrite: bool,
+        #[structopt(name = "yes", short = "y", long = "yes", help = "Don't ask for confirmation before creating the issues")] yes: bool,
+        #[structopt(name = "label", short = "l", long = "label", help = "Add labels to the new issues")] labels: Vec<String>,
+    },
+    #[structopt(name = "start", about = "Create and check out a branch to work on an issue")]
+    Start {
+        #[structopt(name = "assign", short = "a", long = "assign", help = "Assign the issue to yourself")] assign: bool,
+        #[structopt(name = "in-progress", long = "in-progress", help = "Add the in progress label (`in_progress_label` setting) to the issue")]
+        in_progress: bool,
+        #[structopt(help = "Issue number, picked interactively when omitted")] issue: Option<u64>,
+    },
+    #[structopt(name = "close", about = "Close an issue")]
+    Close {
+        #[structopt(help = "Issue number, picked interactively when omitted")] issue: Option<u64>,
+    },
     #[structopt(name = "o", about = "Open issue")]
     OpenIssue {
         #[structopt(name = "open", short = "o", long = "open", help = "Open browser after having created the issue")] open_browser: bool,
         #[structopt(name = "label", short = "l", long = "label", help = "Add labels to the issue")] labels: Vec<String>,
         #[structopt(name = "assignee", short = "a", long = "assignee", help = "Assigne the issue to a user")] assignee: Option<String>,
+        #[structopt(name = "confidential", long = "confidential", help = "Make the issue confidential (gitlab only)")] confidential: bool,
+        #[structopt(name = "due-date", long = "due-date", help = "Due date, as YYYY-MM-DD (gitlab only)")] due_date: Option<String>,
+        #[structopt(name = "weight", long = "weight", help = "Weight of the issue (gitlab only)")] weight: Option<u64>,
+        #[structopt(name = "milestone-id", long = "milestone-id", help = "Id of the milestone (its number on github)")] milestone_id: Option<u64>,
+        #[structopt(name = "epic-id", long = "epic-id", help = "Id of the epic to add the issue to (gitlab only)")] epic_id: Option<u64>,
+        #[structopt(name = "type", long = "type", help = "Issue type: issue, incident or test_case (gitlab only)")] issue_type: Option<String>,
+        #[structopt(name = "no-dup-check", long = "no-dup-check", help = "Don't look for similar open issues before creating this one")]
+        no_dup_check: bool,
+        #[structopt(name = "no-label-check", long = "no-label-check", help = "Don't check that the labels exist in the project")]
+        no_label_check: bool,
+        #[structopt(name = "attach", long = "attach", help = "Upload a file and link it in the description, can be repeated")]
+        attachments: Vec<String>,
+        #[structopt(name = "with-context", long = "with-context",
+                    help = "Add the commit, branch, OS and the output of the `context_commands` setting to the text")]
+        with_context: bool,
+        #[structopt(name = "body-file", long = "body-file", help = "Read the text from a file, `-` for stdin")] body_file: Option<String>,
         title: String,
-        text: Option<String>,
+        #[structopt(help = "Text of the issue, `-` to read it from stdin (fenced as a code block)")] text: Option<String>,
     },
     #[structopt(name = "init", about = "Generate configuration")] Init {},
     #[structopt(name = "l", about = "List all gitlab issues")]
@@ -112,13 +692,194 @@ enum Cmd {
         #[structopt(name = "filter", short = "f", long = "filter", default_value = "open",
                     help = "Filter the issues by state. Possible values are: open, closed")]
         filter_state: IssueFilter,
+        #[structopt(name = "offline", long = "offline", help = "List the issues from the local cache (see `gli fetch`)")] offline: bool,
+    },
+    #[structopt(name = "tui", about = "Browse and triage issues in a full-screen interface")] Tui {},
+    #[structopt(name = "fetch", about = "Update the local cache of issues")]
+    Fetch {
+        #[structopt(name = "full", long = "full", help = "Download every issue again, dropping the deleted and transferred ones")] full: bool,
+    },
+    #[structopt(name = "search", about = "Search issues by text, state, label and author")]
+    Search {
+        #[structopt(name = "filter", short = "f", long = "filter",
+                    help = "Only show issues in this state. Possible values are: open, closed")]
+        filter_state: Option<IssueFilter>,
+        #[structopt(name = "label", short = "l", long = "label", help = "Only show issues with this label")] labels: Vec<String>,
+        #[structopt(name = "author", short = "a", long = "author", help = "Only show issues opened by this user")] author: Option<String>,
+        #[structopt(name = "offline", long = "offline", help = "Search the local cache (see `gli fetch`)")] offline: bool,
+        query: Vec<String>,
+    },
+    #[structopt(name = "status", about = "Show the current account and the remaining API quota")] Status {},
+    #[structopt(name = "queue", about = "Manage issues queued while offline")]
+    Queue {
+        #[structopt(subcommand)] action: QueueAction,
+    },
+    #[structopt(name = "sync", about = "Create the issues queued while offline")] Sync {},
+    #[structopt(name = "mr", about = "Manage merge requests (pull requests on github)")]
+    Mr {
+        #[structopt(subcommand)] action: MrAction,
+    },
+    #[structopt(name = "hook", about = "Manage git hooks")]
+    Hook {
+        #[structopt(subcommand)] action: HookAction,
+    },
+    #[structopt(name = "config", about = "Read or change configuration values")]
+    Config {
+        #[structopt(subcommand)] action: ConfigAction,
     },
 }
 
+#[derive(StructOpt, Debug)]
+enum QueueAction {
+    #[structopt(name = "list", about = "List queued issues")] List {},
+    #[structopt(name = "edit", about = "Edit the title and text of a queued issue")]
+    Edit {
+        #[structopt(name = "id", parse(try_from_str), help = "Id of the queued issue, see `gli queue list`")] id: u64,
+    },
+    #[structopt(name = "drop", about = "Remove an issue from the queue")]
+    Drop {
+        #[structopt(name = "id", parse(try_from_str), help = "Id of the queued issue, see `gli queue list`")] id: u64,
+    },
+    #[structopt(name = "sync", about = "Create the queued issues")] Sync {},
+}
+
+#[derive(StructOpt, Debug)]
+enum MrAction {
+    #[structopt(name = "create", about = "Open a merge request from the current branch")]
+    Create {
+        #[structopt(name = "target", short = "t", long = "target", help = "Branch to merge into, the default branch of the project if omitted")]
+        target: Option<String>,
+        #[structopt(name = "push", short = "p", long = "push", help = "Push the current branch to origin first")] push: bool,
+        #[structopt(name = "title", long = "title", help = "Title, taken from the commits if omitted")] title: Option<String>,
+        #[structopt(name = "message", short = "m", long = "message", help = "Description, taken from the commits if omitted")]
+        message: Option<String>,
+        #[structopt(name = "label", short = "l", long = "label", help = "Add labels to the merge request")] labels: Vec<String>,
+        #[structopt(name = "assignee", short = "a", long = "assignee", help = "Assign the merge request to a user")] assignee: Option<String>,
+        #[structopt(name = "reviewer", short = "r", long = "reviewer", help = "Request a review from a user")] reviewers: Vec<String>,
+        #[structopt(name = "draft", short = "d", long = "draft", help = "Mark the merge request as a draft")] draft: bool,
+        #[structopt(name = "closes", long = "closes", help = "Close this issue when the merge request is merged, the issue of the current branch by default")] closes: Vec<u64>,
+    },
+    #[structopt(name = "list", about = "List merge requests")]
+    List {
+        #[structopt(name = "filter", short = "f", long = "filter", default_value = "open",
+                    help = "Filter the merge requests by state. Possible values are: open, closed, merged, all")]
+        filter_state: MergeRequestFilter,
+        #[structopt(name = "author", short = "a", long = "author", help = "Only show merge requests opened by this user")] author: Option<String>,
+        #[structopt(name = "reviewer", short = "r", long = "reviewer", help = "Only show merge requests this user is asked to review")]
+        reviewer: Option<String>,
+    },
+    #[structopt(name = "show", about = "Show a merge request with its checks and approvals")]
+    Show {
+        #[structopt(name = "number", parse(try_from_str), help = "Number of the merge request")] number: u64,
+    },
+    #[structopt(name = "checkout", about = "Check out the source branch of a merge request, forks included")]
+    Checkout {
+        #[structopt(name = "branch", short = "b", long = "branch", help = "Name of the local branch, the source branch name if omitted")]
+        branch: Option<String>,
+        #[structopt(name = "number", parse(try_from_str), help = "Number of the merge request")] number: u64,
+    },
+    #[structopt(name = "merge", about = "Merge a merge request")]
+    Merge {
+        #[structopt(name = "squash", long = "squash", help = "Squash the commits")] squash: bool,
+        #[structopt(name = "rebase", long = "rebase",
+                    help = "Rebase the commits on the target branch, without merge commit on github and on gitlab projects using fast-forward merges")]
+        rebase: bool,
+        #[structopt(name = "number", parse(try_from_str), help = "Number of the merge request")] number: u64,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum LabelAction {
+    #[structopt(name = "list", about = "List labels with their color and description")] List {},
+    #[structopt(name = "create", about = "Create a label")]
+    Create {
+        #[structopt(name = "color", short = "c", long = "color", help = "Color, eg #d9534f. #ededed if omitted")] color: Option<String>,
+        #[structopt(name = "description", short = "d", long = "description", help = "Description of the label")] description: Option<String>,
+        name: String,
+    },
+    #[structopt(name = "edit", about = "Rename a label or change its color or description")]
+    Edit {
+        #[structopt(name = "new-name", long = "new-name", help = "New name of the label")] new_name: Option<String>,
+        #[structopt(name = "color", short = "c", long = "color", help = "New color, eg #d9534f")] color: Option<String>,
+        #[structopt(name = "description", short = "d", long = "description", help = "New description")] description: Option<String>,
+        name: String,
+    },
+    #[structopt(name = "delete", about = "Delete a label")]
+    Delete {
+        #[structopt(name = "yes", short = "y", long = "yes", help = "Don't ask for confirmation")] yes: bool,
+        name: String,
+    },
+    #[structopt(name = "sync", about = "Create and update labels to match those of another project")]
+    Sync {
+        #[structopt(name = "from", long = "from", help = "Project to copy the labels from, eg github:owner/repo")] from: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum HookAction {
+    #[structopt(name = "install", about = "Install a commit-msg hook checking that commits reference an issue")]
+    Install {
+        #[structopt(name = "force", short = "f", long = "force", help = "Replace an existing commit-msg hook")] force: bool,
+    },
+    #[structopt(name = "commit-msg", about = "Check a commit message, run by the commit-msg hook")] CommitMsg { file: String },
+}
+
+#[derive(StructOpt, Debug)]
+enum ConfigAction {
+    #[structopt(name = "get", about = "Print a configuration value, eg `accounts.0.domain`")] Get { key: String },
+    #[structopt(name = "set", about = "Change a configuration value")] Set { key: String, value: String },
+    #[structopt(name = "list", about = "List all configuration values")] List {},
+    #[structopt(name = "validate", about = "Check that the configuration is usable")] Validate {},
+}
+
 fn main() {
-    let cmd = Cmd::from_args();
-    match do_work(&cmd) {
+    let opt = Opt::from_args();
+    trace::init(opt.verbose);
+    match do_work(&opt.cmd) {
+        Ok(ref str) if str.is_empty() => {}
         Ok(str) => println!("{}", str),
-        Err(e) => println!("Something happened: {}", e),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if let Some(hint) = e.hint() {
+                eprintln!("{}", hint);
+            }
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Opt {
+        Opt::from_clap(Opt::clap().get_matches_from(args.to_vec()))
+    }
+
+    #[test]
+    fn parsing_merge_request_numbers() {
+        match parse(&["gli", "mr", "merge", "12"]).cmd {
+            Cmd::Mr { action: MrAction::Merge { number, .. } } => assert_eq!(number, 12),
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        match parse(&["gli", "mr", "checkout", "42"]).cmd {
+            Cmd::Mr { action: MrAction::Checkout { number, .. } } => assert_eq!(number, 42),
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        assert!(Opt::clap().get_matches_from_safe(vec!["gli", "mr", "show"]).is_err());
+        assert!(Opt::clap().get_matches_from_safe(vec!["gli", "mr", "merge", "twelve"]).is_err());
+    }
+
+    #[test]
+    fn parsing_queue_ids() {
+        match parse(&["gli", "queue", "drop", "3"]).cmd {
+            Cmd::Queue { action: QueueAction::Drop { id } } => assert_eq!(id, 3),
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        match parse(&["gli", "queue", "edit", "3"]).cmd {
+            Cmd::Queue { action: QueueAction::Edit { id } } => assert_eq!(id, 3),
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        assert!(Opt::clap().get_matches_from_safe(vec!["gli", "queue", "drop"]).is_err());
     }
 }
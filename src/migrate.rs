@@ -0,0 +1,236 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use serde_json;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use xdg::BaseDirectories;
+
+/// Progress of one issue, saved after each step so an interrupted migration can be resumed
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MigratedIssue {
+    pub source:   u64,
+    pub target:   u64,
+    /// Number of comments copied so far
+    pub comments: usize,
+    pub closed:   bool,
+}
+
+/// `$XDG_DATA_HOME/issues-helper/migrations/<source domain>/<owner>/<repo>/<target domain>/<owner>/<repo>.json`
+fn state_path(source: &Project, target: &Project) -> Result<PathBuf, GliError> {
+    Ok(BaseDirectories::with_prefix("issues-helper")?.place_data_file(format!(
+        "migrations/{}/{}/{}/{}/{}/{}.json",
+        source.account.domain, source.owner, source.repo, target.account.domain, target.owner, target.repo
+    ))?)
+}
+
+fn load(path: &PathBuf) -> Result<Vec<MigratedIssue>, GliError> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn save(path: &PathBuf, migrated: &[MigratedIssue]) -> Result<(), GliError> {
+    File::create(path)?.write_all(serde_json::to_string_pretty(migrated)?.as_bytes())?;
+    Ok(())
+}
+
+/// Start of the line added to the migrated descriptions, used to find issues created by an interrupted run
+fn origin_marker(issue: &Issue) -> String {
+    format!("*Migrated from {}, ", issue.url)
+}
+
+/// The original description, with where it comes from. Authors are not mentioned with `@`,
+/// the same username may belong to someone else on the other forge.
+pub fn migrated_body(issue: &Issue) -> String {
+    let origin = format!(
+        "{}opened by {} on {}*",
+        origin_marker(issue),
+        issue.author.clone().unwrap_or("?".to_owned()),
+        short_date(&issue.created_at)
+    );
+    match issue.body {
+        Some(ref body) if !body.trim().is_empty() => format!("{}\n\n---\n{}", body, origin),
+        _ => origin,
+    }
+}
+
+pub fn migrated_comment(comment: &Comment) -> String {
+    format!(
+        "**{}** commented on {}:\n\n{}",
+        comment.author.clone().unwrap_or("?".to_owned()),
+        short_date(&comment.created_at),
+        comment.body
+    )
+}
+
+/// Creates the labels of `source` which `target` lacks, returns how many
+fn copy_labels(
+    source_http: &mut HttpClient,
+    source: &Project,
+    target_http: &mut HttpClient,
+    target: &Project,
+) -> Result<usize, GliError> {
+    let existing: Vec<String> = forge::fetch_labels(target_http, target)?
+        .into_iter()
+        .map(|l| l.name.to_lowercase())
+        .collect();
+    let mut created = 0;
+    for label in forge::fetch_labels(source_http, source)? {
+        if !existing.contains(&label.name.to_lowercase()) {
+            forge::create_label(target_http, target, &label)?;
+            created += 1;
+        }
+    }
+    Ok(created)
+}
+
+/// Copy of `issue` created by a run which stopped before saving it, eg because the request timed out
+fn created_copy(issue: &Issue, target_issues: &[Issue]) -> Option<u64> {
+    let marker = origin_marker(issue);
+    target_issues
+        .iter()
+        .find(|i| i.body.as_ref().map(|b| b.contains(&marker)).unwrap_or(false))
+        .map(|i| i.number)
+}
+
+/// Whether `comment` is the last one of the target issue: the run which posted it stopped before
+/// saving it, eg because the request timed out. Only the first comment left to copy can be in that case.
+fn posted_last(comment: &Comment, target_comments: &[Comment]) -> bool {
+    target_comments
+        .last()
+        .map(|c| c.body.trim() == migrated_comment(comment).trim())
+        .unwrap_or(false)
+}
+
+/// Copies every issue of `source` to `target`, oldest first, with labels, comments and state.
+/// Issues already copied by a previous run are skipped (or completed if the run stopped midway).
+/// Confidential issues are only copied to gitlab, where they stay confidential.
+pub fn migrate(config: &Config, from: &str, to: &str) -> Result<String, GliError> {
+    let source = project_from_spec(config, from)?;
+    let target = project_from_spec(config, to)?;
+    let mut source_http = HttpClient::new(&source.account)?;
+    let mut target_http = HttpClient::new(&target.account)?;
+    let path = state_path(&source, &target)?;
+    let mut migrated = load(&path)?;
+
+    let labels = copy_labels(&mut source_http, &source, &mut target_http, &target)?;
+    let mut issues = forge::fetch_issues(&mut source_http, &source, None, None)?;
+    issues.sort_by_key(|i| i.number);
+    let target_issues = forge::fetch_issues(&mut target_http, &target, None, None)?;
+    let keeps_confidential = match &target.place {
+        &Place::Gitlab(_) => true,
+        &Place::Github => false,
+    };
+    let mut skipped = vec![];
+    let total = issues.len();
+    for (n, issue) in issues.iter().enumerate() {
+        eprint!("\rMigrating issue {}/{}", n + 1, total);
+        if issue.confidential && !keeps_confidential {
+            skipped.push(issue.number);
+            continue;
+        }
+        let (i, resumed) = match migrated.iter().position(|m| m.source == issue.number) {
+            Some(i) => (i, true),
+            None => {
+                let number = match created_copy(issue, &target_issues) {
+                    Some(number) => number,
+                    None => {
+                        let new_issue = NewIssue {
+                            title: issue.title.clone(),
+                            text: Some(migrated_body(issue)),
+                            labels: issue.labels.clone(),
+                            confidential: issue.confidential,
+                            ..NewIssue::default()
+                        };
+                        forge::create_issue(&mut target_http, &target, &new_issue)?.0
+                    }
+                };
+                migrated.push(MigratedIssue {
+                    source:   issue.number,
+                    target:   number,
+                    comments: 0,
+                    closed:   false,
+                });
+                save(&path, &migrated)?;
+                (migrated.len() - 1, false)
+            }
+        };
+        let number = migrated[i].target;
+        let comments = forge::fetch_comments(&mut source_http, &source, issue.number)?;
+        if let (true, Some(comment)) = (resumed, comments.get(migrated[i].comments)) {
+            if posted_last(comment, &forge::fetch_comments(&mut target_http, &target, number)?) {
+                migrated[i].comments += 1;
+                save(&path, &migrated)?;
+            }
+        }
+        for comment in comments.iter().skip(migrated[i].comments) {
+            forge::comment_issue(&mut target_http, &target, number, &migrated_comment(comment))?;
+            migrated[i].comments += 1;
+            save(&path, &migrated)?;
+        }
+        if issue.state == "closed" && !migrated[i].closed {
+            forge::set_issue_state(&mut target_http, &target, number, &IssueFilter::Closed)?;
+            migrated[i].closed = true;
+            save(&path, &migrated)?;
+        }
+    }
+    if total > 0 {
+        eprintln!();
+    }
+
+    let mut report = vec![format!(
+        "Migrated {} issue(s) from {} to {}, created {} label(s)",
+        total - skipped.len(),
+        from,
+        to,
+        labels
+    )];
+    report.extend(migrated.iter().map(|m| format!("#{} → #{}", m.source, m.target)));
+    if !skipped.is_empty() {
+        report.push(format!(
+            "Skipped {} confidential issue(s), github has no confidential issues: {}",
+            skipped.len(),
+            skipped.iter().map(|n| format!("#{}", n)).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    report.push(format!("The mapping is kept in {}", path.display()));
+    Ok(report.join("\n"))
+}
+
+#[cfg(test)]
+mod migrate_tests {
+    use super::*;
+
+    #[test]
+    fn attributing_content() {
+        let comment = Comment {
+            author:     Some("alice".to_owned()),
+            body:       "Fixed in 0.3".to_owned(),
+            created_at: "2017-11-02T10:33:44Z".to_owned(),
+        };
+        assert_eq!(migrated_comment(&comment), "**alice** commented on 2017-11-02 10:33:\n\nFixed in 0.3");
+    }
+
+    #[test]
+    fn finding_posted_comments() {
+        let comment = Comment {
+            author:     Some("alice".to_owned()),
+            body:       "Fixed in 0.3".to_owned(),
+            created_at: "2017-11-02T10:33:44Z".to_owned(),
+        };
+        let copy = Comment {
+            author:     Some("gli-bot".to_owned()),
+            body:       migrated_comment(&comment),
+            created_at: "2024-05-01T08:00:00Z".to_owned(),
+        };
+        assert!(posted_last(&comment, &[copy.clone()]));
+        assert!(!posted_last(&comment, &[copy, comment.clone()]));
+        assert!(!posted_last(&comment, &[]));
+    }
+}
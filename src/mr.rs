@@ -0,0 +1,146 @@
+use config::*;
+
+/// `merge request !12` on gitlab, `pull request #12` on github
+pub fn reference(project: &Project, number: u64) -> String {
+    match &project.place {
+        &Place::Gitlab(_) => format!("merge request !{}", number),
+        &Place::Github => format!("pull request #{}", number),
+    }
+}
+
+/// Where the forge keeps the head of a merge request, this works for merge requests from forks too
+pub fn head_ref(project: &Project, number: u64) -> String {
+    match &project.place {
+        &Place::Gitlab(_) => format!("refs/merge-requests/{}/head", number),
+        &Place::Github => format!("refs/pull/{}/head", number),
+    }
+}
+
+/// One line description, as printed by `gli mr list`
+pub fn summary(project: &Project, mr: &MergeRequest) -> String {
+    let sigil = match &project.place {
+        &Place::Gitlab(_) => '!',
+        &Place::Github => '#',
+    };
+    format!(
+        "{}{} {}{} {} ({} → {}) {}",
+        sigil,
+        mr.number,
+        mr.state,
+        if mr.draft { " draft" } else { "" },
+        mr.title,
+        mr.source_branch,
+        mr.target_branch,
+        mr.url
+    )
+}
+
+/// Header, checks, approvals and description, as printed by `gli mr show`
+pub fn details(project: &Project, mr: &MergeRequest, status: &MergeRequestStatus) -> String {
+    let mut text = format!(
+        "{}\nby {} on {}\n",
+        summary(project, mr),
+        mr.author.clone().unwrap_or("?".to_owned()),
+        short_date(&mr.created_at)
+    );
+    if !mr.reviewers.is_empty() {
+        text.push_str(&format!("reviewers: {}\n", mr.reviewers.join(", ")));
+    }
+    if status.checks.is_empty() {
+        text.push_str("checks: none\n");
+    } else {
+        text.push_str("checks:\n");
+        for &(ref name, ref state) in &status.checks {
+            text.push_str(&format!("  {}: {}\n", name, state));
+        }
+    }
+    text.push_str(&format!(
+        "approved by: {}",
+        if status.approved_by.is_empty() {
+            "nobody".to_owned()
+        } else {
+            status.approved_by.join(", ")
+        }
+    ));
+    if let Some(left) = status.approvals_left {
+        text.push_str(&format!(" ({} more needed)", left));
+    }
+    text.push_str(&format!("\n\n{}\n", mr.body.clone().unwrap_or_default()));
+    text
+}
+
+/// `fix-config-crash` becomes `Fix config crash`
+fn humanize(branch: &str) -> String {
+    let words = branch
+        .replace(|c: char| c == '-' || c == '_' || c == '/', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Title and description of a merge request, from the messages of its commits (oldest first):
+/// a single commit gives its subject and body, several commits give the branch name and a list of subjects
+pub fn prefill(branch: &str, messages: &[String]) -> (String, Option<String>) {
+    match messages.len() {
+        0 => (humanize(branch), None),
+        1 => {
+            let mut parts = messages[0].splitn(2, '\n');
+            let title = parts.next().unwrap_or("").trim().to_owned();
+            let body = parts.next().unwrap_or("").trim().to_owned();
+            (title, if body.is_empty() { None } else { Some(body) })
+        }
+        _ => (
+            humanize(branch),
+            Some(
+                messages
+                    .iter()
+                    .map(|m| format!("- {}", m.lines().next().unwrap_or("")))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ),
+    }
+}
+
+/// Appends `Closes #N` lines, so the forge closes the issues once the merge request is merged
+pub fn with_closing_references(body: Option<String>, issues: &[u64]) -> Option<String> {
+    if issues.is_empty() {
+        return body;
+    }
+    let closes = issues
+        .iter()
+        .map(|n| format!("Closes #{}", n))
+        .collect::<Vec<_>>()
+        .join("\n");
+    match body {
+        Some(body) => Some(format!("{}\n\n{}", body, closes)),
+        None => Some(closes),
+    }
+}
+
+#[cfg(test)]
+mod prefill_tests {
+    use super::*;
+
+    #[test]
+    fn prefilling_from_commits() {
+        assert_eq!(prefill("fix-config_crash", &[]), ("Fix config crash".to_owned(), None));
+        assert_eq!(
+            prefill("fix", &["Read config lazily\n\nIt crashed when missing".to_owned()]),
+            ("Read config lazily".to_owned(), Some("It crashed when missing".to_owned()))
+        );
+        assert_eq!(
+            prefill("fix", &["First\n\nbody".to_owned(), "Second".to_owned()]),
+            ("Fix".to_owned(), Some("- First\n- Second".to_owned()))
+        );
+        assert_eq!(
+            with_closing_references(Some("Body".to_owned()), &[3, 4]),
+            Some("Body\n\nCloses #3\nCloses #4".to_owned())
+        );
+    }
+}
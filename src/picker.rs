@@ -0,0 +1,175 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use std::cmp;
+use std::io::{self, ErrorKind, Write};
+use std::process::{Command, Stdio};
+use termion;
+use termion::clear;
+use termion::cursor::Goto;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use termion::style;
+use tui::fit;
+
+/// Scores `text` against `pattern` if every character of the pattern appears in order, case insensitive.
+/// Consecutive matches and matches at the start of a word score higher.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for p in pattern.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = match text[position..].iter().position(|&c| c == p) {
+            Some(offset) => position + offset,
+            None => return None,
+        };
+        score += 1;
+        if previous.map(|prev| prev + 1 == found).unwrap_or(false) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+fn describe(issue: &Issue) -> String {
+    if issue.labels.is_empty() {
+        format!("#{} {}", issue.number, issue.title)
+    } else {
+        format!("#{} {} [{}]", issue.number, issue.title, issue.labels.join(", "))
+    }
+}
+
+/// Number at the start of a `#12 title` line
+fn number_of(line: &str) -> Option<u64> {
+    line.trim_left_matches('#')
+        .split_whitespace()
+        .next()
+        .and_then(|n| n.parse().ok())
+}
+
+fn no_selection() -> GliError {
+    GliError::Other("No issue selected".to_owned())
+}
+
+/// Lets `fzf` do the picking, `None` if it isn't installed
+fn pick_with_fzf(lines: &[String]) -> Result<Option<u64>, GliError> {
+    let mut child = match Command::new("fzf")
+        .args(&["--prompt", "issue> ", "--no-multi"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    child
+        .stdin
+        .take()
+        .expect("fzf stdin is piped")
+        .write_all(lines.join("\n").as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(no_selection());
+    }
+    number_of(&String::from_utf8_lossy(&output.stdout))
+        .map(Some)
+        .ok_or_else(no_selection)
+}
+
+fn pick_builtin(lines: &[String]) -> Result<u64, GliError> {
+    let mut out = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut keys = io::stdin().keys();
+    let mut pattern = String::new();
+    let mut selected = 0;
+    loop {
+        let mut matches: Vec<(i64, &String)> = lines
+            .iter()
+            .filter_map(|l| fuzzy_score(&pattern, l).map(|s| (s, l)))
+            .collect();
+        // Stable sort, so equal scores keep the most recently updated first
+        matches.sort_by(|a, b| b.0.cmp(&a.0));
+        selected = cmp::min(selected, matches.len().saturating_sub(1));
+
+        let (width, height) = termion::terminal_size()?;
+        write!(out, "{}{}issue> {}", clear::All, Goto(1, 1), pattern)?;
+        for (row, &(_, line)) in matches.iter().take(height as usize - 1).enumerate() {
+            let line = fit(line, width as usize);
+            write!(out, "{}", Goto(1, row as u16 + 2))?;
+            if row == selected {
+                write!(out, "{}{}{}", style::Invert, line, style::Reset)?;
+            } else {
+                write!(out, "{}", line)?;
+            }
+        }
+        write!(out, "{}", Goto(pattern.chars().count() as u16 + 8, 1))?;
+        out.flush()?;
+
+        match keys.next() {
+            Some(Ok(Key::Char('\n'))) => {
+                return matches
+                    .get(selected)
+                    .and_then(|&(_, line)| number_of(line))
+                    .ok_or_else(no_selection)
+            }
+            Some(Ok(Key::Esc)) | Some(Ok(Key::Ctrl('c'))) | None => return Err(no_selection()),
+            Some(Ok(Key::Down)) | Some(Ok(Key::Ctrl('n'))) => selected += 1,
+            Some(Ok(Key::Up)) | Some(Ok(Key::Ctrl('p'))) => selected = selected.saturating_sub(1),
+            Some(Ok(Key::Backspace)) => {
+                pattern.pop();
+                selected = 0;
+            }
+            Some(Ok(Key::Char(c))) => {
+                pattern.push(c);
+                selected = 0;
+            }
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    }
+}
+
+/// Asks the user to choose one of the open issues, with `fzf` when it's installed
+pub fn pick_issue(http: &mut HttpClient, project: &Project) -> Result<u64, GliError> {
+    let mut issues = forge::fetch_issues(http, project, Some(&IssueFilter::Open), None)?;
+    if issues.is_empty() {
+        return Err(GliError::NotFound(format!("open issue in {}", project.name())));
+    }
+    issues.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    let lines: Vec<String> = issues.iter().map(describe).collect();
+    match pick_with_fzf(&lines)? {
+        Some(number) => Ok(number),
+        None => pick_builtin(&lines),
+    }
+}
+
+/// The given issue number, or one picked interactively
+pub fn issue_or_pick(http: &mut HttpClient, project: &Project, issue: Option<u64>) -> Result<u64, GliError> {
+    match issue {
+        Some(number) => Ok(number),
+        None => pick_issue(http, project),
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_tests {
+    use super::*;
+
+    #[test]
+    fn scoring_matches() {
+        assert_eq!(fuzzy_score("", "#12 Crash on startup"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "#12 Crash on startup"), None);
+        assert!(fuzzy_score("cos", "#12 Crash on startup").is_some());
+        assert!(fuzzy_score("crash", "#12 Crash on startup") > fuzzy_score("crash", "#13 Can reuse a stash"));
+        assert_eq!(number_of("#12 Crash on startup [bug]\n"), Some(12));
+    }
+}
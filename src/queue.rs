@@ -0,0 +1,222 @@
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use serde_json;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use xdg::BaseDirectories;
+
+/// An issue that couldn't be created because the network was down
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueuedIssue {
+    pub id:        u64,
+    pub domain:    String,
+    pub owner:     String,
+    pub repo:      String,
+    pub queued_at: u64,
+    pub issue:     NewIssue,
+}
+
+fn queue_path() -> Result<PathBuf, GliError> {
+    Ok(BaseDirectories::with_prefix("issues-helper")?.place_data_file("queue.json")?)
+}
+
+fn load() -> Result<Vec<QueuedIssue>, GliError> {
+    let path = queue_path()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn save(queue: &Vec<QueuedIssue>) -> Result<(), GliError> {
+    let mut f = File::create(queue_path()?)?;
+    f.write_all(serde_json::to_string_pretty(queue)?.as_bytes())?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn ago(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        s if s < 3600 => format!("{} min ago", s / 60),
+        s if s < 86400 => format!("{} h ago", s / 3600),
+        s => format!("{} days ago", s / 86400),
+    }
+}
+
+/// Stores the issue for a later `gli queue sync`, returns its queue id
+pub fn enqueue(project: &Project, issue: &NewIssue) -> Result<u64, GliError> {
+    let mut queue = load()?;
+    let id = queue.iter().map(|q| q.id).max().unwrap_or(0) + 1;
+    queue.push(QueuedIssue {
+        id,
+        domain: project.account.domain.clone(),
+        owner: project.owner.clone(),
+        repo: project.repo.clone(),
+        queued_at: now(),
+        issue: issue.clone(),
+    });
+    save(&queue)?;
+    Ok(id)
+}
+
+pub fn list() -> Result<String, GliError> {
+    let queue = load()?;
+    if queue.is_empty() {
+        return Ok("The queue is empty".to_owned());
+    }
+    Ok(queue
+        .iter()
+        .map(|q| {
+            format!(
+                "{} {}/{}/{} {} ({})",
+                q.id,
+                q.domain,
+                q.owner,
+                q.repo,
+                q.issue.title,
+                ago(q.queued_at)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+pub fn remove(id: u64) -> Result<String, GliError> {
+    let mut queue = load()?;
+    let before = queue.len();
+    queue.retain(|q| q.id != id);
+    if queue.len() == before {
+        return Err(GliError::NotFound(format!("queued issue {}", id)));
+    }
+    save(&queue)?;
+    Ok(format!("Dropped queued issue {}", id))
+}
+
+/// Opens `$VISUAL` / `$EDITOR` on the title (first line) and body (the rest) of a queued issue
+pub fn edit(id: u64) -> Result<String, GliError> {
+    let mut queue = load()?;
+    {
+        let queued = queue
+            .iter_mut()
+            .find(|q| q.id == id)
+            .ok_or_else(|| GliError::NotFound(format!("queued issue {}", id)))?;
+        let path = env::temp_dir().join(format!("gli-queued-{}.md", id));
+        File::create(&path)?.write_all(
+            format!(
+                "{}\n\n{}\n",
+                queued.issue.title,
+                queued.issue.text.clone().unwrap_or_default()
+            ).as_bytes(),
+        )?;
+
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or("vi".to_owned());
+        let status = Command::new(&editor).arg(&path).status()?;
+        if !status.success() {
+            return Err(format!("{} exited with {}, the queued issue was not changed", editor, status).into());
+        }
+
+        let mut contents = String::new();
+        File::open(&path)?.read_to_string(&mut contents)?;
+        fs::remove_file(&path)?;
+        let mut lines = contents.splitn(2, '\n');
+        let title = lines.next().unwrap_or("").trim().to_owned();
+        if title.is_empty() {
+            return Err("The title can't be empty, the queued issue was not changed".into());
+        }
+        let text = lines.next().unwrap_or("").trim().to_owned();
+        queued.issue.title = title;
+        queued.issue.text = if text.is_empty() { None } else { Some(text) };
+    }
+    save(&queue)?;
+    Ok(format!("Updated queued issue {}", id))
+}
+
+/// Submits every queued issue, oldest first. Issues are removed from the queue once created,
+/// syncing stops at the first network error since the following ones would fail too, or at the
+/// first timeout since the issue may have been created. The report is printed and the first
+/// error returned when some issues are left in the queue, so the exit code tells about it.
+pub fn sync(config: &Config) -> Result<String, GliError> {
+    let queue = load()?;
+    if queue.is_empty() {
+        return Ok("The queue is empty".to_owned());
+    }
+    let mut clients: HashMap<String, HttpClient> = HashMap::new();
+    let mut remaining = vec![];
+    let mut report = vec![];
+    let mut offline = false;
+    let mut first_error = None;
+
+    for queued in queue {
+        if offline {
+            remaining.push(queued);
+            continue;
+        }
+        let created = project_for(
+            config,
+            queued.domain.clone(),
+            queued.owner.clone(),
+            queued.repo.clone(),
+        ).and_then(|project| {
+            if !clients.contains_key(&queued.domain) {
+                clients.insert(queued.domain.clone(), HttpClient::new(&project.account)?);
+            }
+            let http = clients.get_mut(&queued.domain).unwrap();
+            forge::create_issue(http, &project, &queued.issue)
+        });
+
+        match created {
+            Ok((number, url)) => report.push(format!(
+                "Created issue #{} {} (queued as {})",
+                number, url, queued.id
+            )),
+            Err(e) => {
+                match e {
+                    GliError::Network(_) => offline = true,
+                    GliError::Timeout(_) => {
+                        offline = true;
+                        report.push(format!(
+                            "Queued issue {} may have been created anyway, check and run `gli queue drop {}` if so",
+                            queued.id, queued.id
+                        ));
+                    }
+                    _ => {}
+                }
+                report.push(format!("Couldn't create queued issue {}: {}", queued.id, e));
+                remaining.push(queued);
+                if first_error.is_none() {
+                    first_error = Some(e);
+                }
+            }
+        }
+    }
+    save(&remaining)?;
+    if !remaining.is_empty() {
+        report.push(format!("{} issue(s) left in the queue", remaining.len()));
+    }
+    match first_error {
+        Some(e) => {
+            println!("{}", report.join("\n"));
+            Err(e)
+        }
+        None => Ok(report.join("\n")),
+    }
+}
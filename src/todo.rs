@@ -0,0 +1,331 @@
+use browse;
+use browse::Page;
+use config::*;
+use errors::GliError;
+use forge;
+use git;
+use git2::Repository;
+use hook::issue_references;
+use http::HttpClient;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+const KEYWORDS: &'static [&'static str] = &["TODO", "FIXME", "XXX"];
+const C_MARKERS: &'static [&'static str] = &["//", "/*"];
+const HASH_MARKERS: &'static [&'static str] = &["#"];
+const DASH_MARKERS: &'static [&'static str] = &["--"];
+const SEMICOLON_MARKERS: &'static [&'static str] = &[";"];
+const XML_MARKERS: &'static [&'static str] = &["<!--"];
+/// For unknown file types, `--` and `;` are left out since they're common in code (`i--;`)
+const DEFAULT_MARKERS: &'static [&'static str] = &["//", "#", "/*", "<!--"];
+/// Bigger files are skipped, they're most likely generated
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// A `TODO`, `FIXME` or `XXX` comment
+#[derive(Debug)]
+pub struct Todo {
+    /// Relative to the repository root
+    pub path:        String,
+    /// Starting at 1
+    pub line:        usize,
+    pub keyword:     String,
+    pub text:        String,
+    pub references:  Vec<u64>,
+    /// Byte offset of the end of the keyword in the line
+    pub keyword_end: usize,
+    pub source:      String,
+}
+
+/// How comments start in this file, guessed from its extension
+pub fn comment_markers(path: &str) -> &'static [&'static str] {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if name == "Makefile" || name == "Dockerfile" {
+        return HASH_MARKERS;
+    }
+    let extension = match name.rfind('.') {
+        Some(i) => name[i + 1..].to_lowercase(),
+        None => return DEFAULT_MARKERS,
+    };
+    match extension.as_str() {
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "kts" | "scala" | "groovy" | "go" | "swift" | "js" | "jsx"
+        | "ts" | "tsx" | "dart" | "css" | "scss" | "less" | "proto" => C_MARKERS,
+        "py" | "rb" | "sh" | "bash" | "zsh" | "pl" | "r" | "yml" | "yaml" | "toml" | "conf" | "cfg" | "mk" | "cmake" | "ex" | "exs"
+        | "nix" | "tf" | "ps1" => HASH_MARKERS,
+        "sql" | "lua" | "hs" | "elm" | "ada" => DASH_MARKERS,
+        "clj" | "cljs" | "el" | "lisp" | "scm" | "asm" | "s" | "ini" => SEMICOLON_MARKERS,
+        "html" | "htm" | "xml" | "svg" | "md" | "markdown" => XML_MARKERS,
+        _ => DEFAULT_MARKERS,
+    }
+}
+
+/// Finds the keyword of a `TODO` comment in a line of code, `markers` being how comments start.
+/// Returns the keyword, the text of the comment and where the keyword ends.
+pub fn parse_line(line: &str, markers: &[&str]) -> Option<(&'static str, String, usize)> {
+    for &keyword in KEYWORDS {
+        let mut from = 0;
+        while let Some(i) = line[from..].find(keyword) {
+            let start = from + i;
+            let end = start + keyword.len();
+            from = end;
+            let before = line[..start].chars().last();
+            let after = line[end..].chars().next();
+            let whole_word = !before.map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false)
+                && !after.map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false);
+            // The following lines of a `/* */` block usually start with `*`
+            let in_block = markers.contains(&"/*") && line.trim_left().starts_with('*');
+            let in_comment = markers.iter().any(|m| line[..start].contains(m)) || in_block;
+            if !whole_word || !in_comment {
+                continue;
+            }
+            let mut text = line[end..].trim();
+            // `TODO(alice):` or `TODO(#12)`
+            if text.starts_with('(') {
+                text = match text.find(')') {
+                    Some(close) => &text[close + 1..],
+                    None => text,
+                };
+            }
+            let text = text.trim_left_matches(|c: char| c == ':' || c == '-' || c.is_whitespace())
+                .trim_right_matches("-->")
+                .trim_right_matches("*/")
+                .trim();
+            return Some((keyword, text.to_owned(), end));
+        }
+    }
+    None
+}
+
+fn scan_file(root: &Path, path: &Path, todos: &mut Vec<Todo>) -> Result<(), GliError> {
+    if fs::metadata(path)?.len() > MAX_FILE_SIZE {
+        return Ok(());
+    }
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(_) => return Ok(()),
+    };
+    let relative = path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    let markers = comment_markers(&relative);
+    for (i, line) in content.split('\n').enumerate() {
+        let line = line.trim_right_matches('\r');
+        if let Some((keyword, text, keyword_end)) = parse_line(line, markers) {
+            todos.push(Todo {
+                path:        relative.clone(),
+                line:        i + 1,
+                keyword:     keyword.to_owned(),
+                text:        text,
+                references:  issue_references(&line[keyword_end..]),
+                keyword_end: keyword_end,
+                source:      line.trim().to_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn scan_dir(repo: &Repository, root: &Path, dir: &Path, todos: &mut Vec<Todo>) -> Result<(), GliError> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path.ends_with(".git") {
+            continue;
+        }
+        if let Ok(relative) = path.strip_prefix(root) {
+            if repo.status_should_ignore(relative)? {
+                continue;
+            }
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            scan_dir(repo, root, &path, todos)?;
+        } else if file_type.is_file() {
+            scan_file(root, &path, todos)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every `TODO`, `FIXME` and `XXX` comment of the working tree, ignored files excluded
+pub fn scan() -> Result<Vec<Todo>, GliError> {
+    let repo = git::open_repository()?;
+    let root = repo.workdir()
+        .ok_or_else(|| GliError::Other("This is a bare repository".to_owned()))?
+        .to_path_buf();
+    let mut todos = vec![];
+    scan_dir(&repo, &root, &root, &mut todos)?;
+    Ok(todos)
+}
+
+/// Comments which don't reference an issue yet
+pub fn pending(todos: &[Todo]) -> Vec<&Todo> {
+    todos.iter().filter(|t| t.references.is_empty()).collect()
+}
+
+pub fn list(todos: &[Todo]) -> String {
+    let pending: Vec<String> = todos
+        .iter()
+        .filter(|t| t.references.is_empty())
+        .map(|t| format!("{}:{} {} {}", t.path, t.line, t.keyword, t.text))
+        .collect();
+    let referenced = todos.len() - pending.len();
+    let mut report = pending.join("\n");
+    if referenced > 0 {
+        if !report.is_empty() {
+            report.push('\n');
+        }
+        report.push_str(&format!("{} more already reference an issue", referenced));
+    }
+    if report.is_empty() {
+        report = "No TODO, FIXME or XXX comments".to_owned();
+    }
+    report
+}
+
+fn title(todo: &Todo) -> String {
+    if todo.text.is_empty() {
+        format!("{} in {}:{}", todo.keyword, todo.path, todo.line)
+    } else {
+        todo.text.clone()
+    }
+}
+
+/// The issues which would be created, one per line
+pub fn preview(todos: &[&Todo], labels: &[String]) -> String {
+    todos
+        .iter()
+        .enumerate()
+        .map(|(i, todo)| {
+            let mut line = format!("{}. {} ({}:{})", i + 1, title(todo), todo.path, todo.line);
+            if !labels.is_empty() {
+                line.push_str(&format!(" [{}]", labels.join(", ")));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn new_issue(project: &Project, todo: &Todo, sha: &str, labels: &[String]) -> Result<NewIssue, GliError> {
+    let permalink = browse::page_url(
+        project,
+        &Page::File {
+            path:      Some(todo.path.clone()),
+            reference: sha.to_owned(),
+            is_dir:    false,
+            lines:     Some((todo.line as u64, None)),
+        },
+    );
+    let mut body = format!(
+        "`{}` comment in [{}:{}]({}):\n\n```\n{}\n```\n",
+        todo.keyword, todo.path, todo.line, permalink, todo.source
+    );
+    if let Some(author) = git::blame_author(&todo.path, todo.line)? {
+        body.push_str(&format!("\nWritten by {}\n", author));
+    }
+    Ok(NewIssue {
+        title: title(todo),
+        text: Some(body),
+        labels: labels.to_vec(),
+        ..NewIssue::default()
+    })
+}
+
+/// `TODO: x` becomes `TODO(#12): x`, `TODO(alice): x` becomes `TODO(#12, alice): x`
+pub fn add_reference(line: &str, keyword_end: usize, number: u64) -> String {
+    if line[keyword_end..].starts_with('(') {
+        format!("{}(#{}, {}", &line[..keyword_end], number, &line[keyword_end + 1..])
+    } else {
+        format!("{}(#{}){}", &line[..keyword_end], number, &line[keyword_end..])
+    }
+}
+
+/// Adds the issue numbers to the comments, in place
+fn rewrite(todo: &Todo, number: u64) -> Result<(), GliError> {
+    let repo = git::open_repository()?;
+    let path = repo.workdir()
+        .ok_or_else(|| GliError::Other("This is a bare repository".to_owned()))?
+        .join(&todo.path);
+    let mut content = String::new();
+    File::open(&path)?.read_to_string(&mut content)?;
+    let mut lines: Vec<String> = content.split('\n').map(|l| l.to_owned()).collect();
+    if let Some(line) = lines.get_mut(todo.line - 1) {
+        *line = add_reference(line, todo.keyword_end, number);
+    }
+    File::create(&path)?.write_all(lines.join("\n").as_bytes())?;
+    Ok(())
+}
+
+/// Opens an issue for each of the comments. A failing comment doesn't stop the others, except for
+/// network errors and timeouts, as in `gli import`. Every issue created is reported.
+pub fn create(http: &mut HttpClient, project: &Project, todos: &[&Todo], labels: &[String], rewrite_comments: bool) -> Result<String, GliError> {
+    let sha = git::commit_sha(None)?;
+    let mut report = vec![];
+    let mut created = 0;
+    for todo in todos {
+        let result = new_issue(project, todo, &sha, labels).and_then(|issue| forge::create_issue(http, project, &issue));
+        match result {
+            Ok((number, url)) => {
+                created += 1;
+                report.push(format!("{}:{} Created issue #{} {}", todo.path, todo.line, number, url));
+                if rewrite_comments {
+                    if let Err(e) = rewrite(todo, number) {
+                        report.push(format!("{}:{} Couldn't add #{} to the comment: {}", todo.path, todo.line, number, e));
+                    }
+                }
+            }
+            Err(GliError::Network(e)) => {
+                report.push(format!("Couldn't reach {} ({}), stopped at {}:{}", project.account.domain, e, todo.path, todo.line));
+                break;
+            }
+            Err(GliError::Timeout(e)) => {
+                report.push(format!(
+                    "No answer from {} ({}), the issue for {}:{} may have been created, check before running again",
+                    project.account.domain, e, todo.path, todo.line
+                ));
+                break;
+            }
+            Err(e) => report.push(format!("{}:{} Couldn't create the issue: {}", todo.path, todo.line, e)),
+        }
+    }
+    report.push(format!("{} of {} issue(s) created", created, todos.len()));
+    Ok(report.join("\n"))
+}
+
+#[cfg(test)]
+mod todo_tests {
+    use super::*;
+
+    #[test]
+    fn parsing_comments() {
+        let (keyword, text, end) = parse_line("    // TODO(alice): handle errors", C_MARKERS).unwrap();
+        assert_eq!((keyword, text.as_str(), end), ("TODO", "handle errors", 11));
+        assert_eq!(parse_line("# FIXME retry on 502", HASH_MARKERS).map(|t| t.1), Some("retry on 502".to_owned()));
+        assert_eq!(parse_line("/* XXX */", C_MARKERS).map(|t| t.1), Some("".to_owned()));
+        assert!(parse_line("const KEYWORDS = [\"TODO\"];", DEFAULT_MARKERS).is_none());
+        assert!(parse_line("// TODOS are fine", C_MARKERS).is_none());
+    }
+
+    #[test]
+    fn picking_comment_markers() {
+        assert_eq!(comment_markers("src/main.rs"), C_MARKERS);
+        assert_eq!(comment_markers("db/schema.sql"), DASH_MARKERS);
+        assert_eq!(comment_markers("Makefile"), HASH_MARKERS);
+        assert!(parse_line("count--; XXX", DEFAULT_MARKERS).is_none());
+        assert!(parse_line("a; TODO", DEFAULT_MARKERS).is_none());
+        assert!(parse_line("a; TODO", C_MARKERS).is_none());
+        assert!(parse_line("-- TODO index this", DASH_MARKERS).is_some());
+    }
+
+    #[test]
+    fn adding_references() {
+        assert_eq!(add_reference("// TODO: fix", 7, 12), "// TODO(#12): fix");
+        assert_eq!(add_reference("// TODO(alice): fix", 7, 12), "// TODO(#12, alice): fix");
+    }
+}
@@ -0,0 +1,51 @@
+use hyper::header::Headers;
+use std::cmp;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+/// Method, URL, status and timing of each API call
+pub const INFO: usize = 1;
+/// Headers and bodies too
+pub const DEBUG: usize = 2;
+
+static LEVEL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+const SECRET_HEADERS: &'static [&'static str] = &["private-token", "authorization", "proxy-authorization"];
+
+/// Verbosity is the highest of `-v` occurrences and `GLI_LOG` (`info`, `debug`, or a number)
+pub fn init(verbose: u64) {
+    let from_env = match env::var("GLI_LOG").map(|l| l.to_lowercase()) {
+        Ok(ref l) if l == "info" => INFO,
+        Ok(ref l) if l == "debug" || l == "trace" => DEBUG,
+        Ok(l) => l.parse().unwrap_or(0),
+        Err(_) => 0,
+    };
+    LEVEL.store(cmp::max(verbose as usize, from_env), Ordering::Relaxed);
+}
+
+pub fn enabled(level: usize) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+pub fn log(level: usize, message: &str) {
+    if enabled(level) {
+        for line in message.lines() {
+            eprintln!("[gli] {}", line);
+        }
+    }
+}
+
+/// One `Name: value` line per header, with credentials hidden
+pub fn format_headers(headers: &Headers) -> String {
+    headers
+        .iter()
+        .map(|h| {
+            if SECRET_HEADERS.contains(&h.name().to_lowercase().as_str()) {
+                format!("{}: [redacted]", h.name())
+            } else {
+                format!("{}: {}", h.name(), h.value_string())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
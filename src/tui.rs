@@ -0,0 +1,354 @@
+use cache;
+use config::*;
+use errors::GliError;
+use forge;
+use http::HttpClient;
+use open;
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{self, Stdin, Stdout, Write};
+use termion;
+use termion::clear;
+use termion::cursor::{self, Goto};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::AlternateScreen;
+use termion::style;
+
+type Screen = AlternateScreen<RawTerminal<Stdout>>;
+
+const HELP: &'static str =
+    "j/k move  / filter  s state  enter comments  o open  c close/reopen  l label  a assign  m comment  r reload  q quit";
+
+struct App<'a> {
+    http:     &'a mut HttpClient,
+    project:  &'a Project,
+    state:    Option<IssueFilter>,
+    issues:   Vec<Issue>,
+    filter:   String,
+    selected: usize,
+    offset:   usize,
+    comments: HashMap<u64, Vec<Comment>>,
+    message:  String,
+}
+
+impl<'a> App<'a> {
+    fn reload(&mut self) -> Result<(), GliError> {
+        self.issues = forge::fetch_issues(self.http, self.project, self.state.as_ref(), None)?;
+        self.issues.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        self.comments.clear();
+        self.selected = 0;
+        self.offset = 0;
+        self.message = format!(
+            "{} {} issue(s)",
+            self.issues.len(),
+            self.state
+                .as_ref()
+                .map(|s| s.to_string())
+                .unwrap_or("open and closed".to_owned())
+        );
+        Ok(())
+    }
+
+    /// Indices in `issues` of the issues matching the filter
+    fn visible(&self) -> Vec<usize> {
+        let matching: Vec<u64> = cache::search(&self.issues, &self.filter)
+            .iter()
+            .map(|i| i.number)
+            .collect();
+        (0..self.issues.len())
+            .filter(|&i| matching.contains(&self.issues[i].number))
+            .collect()
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.visible().get(self.selected).cloned()
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let len = self.visible().len() as isize;
+        if len == 0 {
+            return;
+        }
+        self.selected = cmp::max(0, cmp::min(len - 1, self.selected as isize + delta)) as usize;
+    }
+
+    /// Runs an API call on the selected issue, reporting the outcome in the status line
+    fn on_current<F>(&mut self, action: F)
+    where
+        F: FnOnce(&mut HttpClient, &Project, &mut Issue) -> Result<String, GliError>,
+    {
+        if let Some(i) = self.current() {
+            self.message = match action(self.http, self.project, &mut self.issues[i]) {
+                Ok(m) => m,
+                Err(e) => format!("Error: {}", e),
+            };
+        }
+    }
+
+    fn draw(&mut self, out: &mut Screen) -> Result<(), GliError> {
+        let (width, height) = termion::terminal_size()?;
+        let (width, height) = (width as usize, height as usize);
+        let list_width = cmp::max(width * 2 / 5, 20);
+        let rows = height.saturating_sub(2);
+        let visible = self.visible();
+
+        if self.selected >= visible.len() {
+            self.selected = visible.len().saturating_sub(1);
+        }
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if rows > 0 && self.selected >= self.offset + rows {
+            self.offset = self.selected + 1 - rows;
+        }
+
+        write!(out, "{}{}", clear::All, Goto(1, 1))?;
+        let header = format!(
+            "{} [{}] /{}",
+            self.project.name(),
+            self.state
+                .as_ref()
+                .map(|s| s.to_string())
+                .unwrap_or("all".to_owned()),
+            self.filter
+        );
+        write!(out, "{}{}{}", style::Bold, fit(&header, width), style::Reset)?;
+
+        for (row, &i) in visible.iter().skip(self.offset).take(rows).enumerate() {
+            let issue = &self.issues[i];
+            let line = fit(&format!("#{} {} {}", issue.number, issue.state, issue.title), list_width - 1);
+            write!(out, "{}", Goto(1, row as u16 + 2))?;
+            if self.offset + row == self.selected {
+                write!(out, "{}{}{}", style::Invert, line, style::Reset)?;
+            } else {
+                write!(out, "{}", line)?;
+            }
+        }
+
+        if let Some(&i) = visible.get(self.selected) {
+            let preview_width = width.saturating_sub(list_width + 1);
+            let lines = preview(&self.issues[i], self.comments.get(&self.issues[i].number), preview_width);
+            for (row, line) in lines.iter().take(rows).enumerate() {
+                write!(out, "{}{}", Goto(list_width as u16 + 1, row as u16 + 2), line)?;
+            }
+        }
+
+        let status = if self.message.is_empty() { HELP } else { self.message.as_str() };
+        write!(out, "{}{}", Goto(1, height as u16), fit(status, width))?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Cuts `text` to `width` characters and pads it with spaces
+pub fn fit(text: &str, width: usize) -> String {
+    let mut line: String = text.chars().take(width).collect();
+    let len = line.chars().count();
+    line.extend(::std::iter::repeat(' ').take(width - len));
+    line
+}
+
+/// Splits `text` in lines of at most `width` characters, on spaces when possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    if width == 0 {
+        return lines;
+    }
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let (line_len, word_len) = (line.chars().count(), word.chars().count());
+            if line_len > 0 && line_len + 1 + word_len > width {
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            while line.chars().count() > width {
+                let rest: String = line.chars().skip(width).collect();
+                lines.push(line.chars().take(width).collect());
+                line = rest;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn preview(issue: &Issue, comments: Option<&Vec<Comment>>, width: usize) -> Vec<String> {
+    let text = match comments {
+        Some(comments) => issue.details(comments),
+        None => format!("{}\n(press enter to load the comments)", issue.details(&[])),
+    };
+    wrap(&text, width)
+}
+
+/// Reads a line of text on the status line, `None` if cancelled with Esc
+fn read_line(out: &mut Screen, keys: &mut Keys<Stdin>, label: &str) -> Result<Option<String>, GliError> {
+    let (width, height) = termion::terminal_size()?;
+    let mut input = String::new();
+    write!(out, "{}", cursor::Show)?;
+    let result = loop {
+        write!(
+            out,
+            "{}{}{}",
+            Goto(1, height),
+            fit(&format!("{}: {}", label, input), width as usize - 1),
+            Goto((label.chars().count() + input.chars().count() + 3) as u16, height)
+        )?;
+        out.flush()?;
+        match keys.next() {
+            Some(Ok(Key::Char('\n'))) => break Some(input),
+            Some(Ok(Key::Esc)) | None => break None,
+            Some(Ok(Key::Backspace)) => {
+                input.pop();
+            }
+            Some(Ok(Key::Char(c))) => input.push(c),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    };
+    write!(out, "{}", cursor::Hide)?;
+    Ok(result)
+}
+
+/// Full-screen issue list with a preview pane, until the user quits
+pub fn run(http: &mut HttpClient, project: &Project) -> Result<String, GliError> {
+    let mut app = App {
+        http:     http,
+        project:  project,
+        state:    Some(IssueFilter::Open),
+        issues:   vec![],
+        filter:   String::new(),
+        selected: 0,
+        offset:   0,
+        comments: HashMap::new(),
+        message:  String::new(),
+    };
+    app.reload()?;
+
+    let mut out = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut keys = io::stdin().keys();
+    write!(out, "{}", cursor::Hide)?;
+    loop {
+        app.draw(&mut out)?;
+        let key = match keys.next() {
+            Some(key) => key?,
+            None => break,
+        };
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => break,
+            Key::Char('j') | Key::Down => app.move_by(1),
+            Key::Char('k') | Key::Up => app.move_by(-1),
+            Key::PageDown => app.move_by(10),
+            Key::PageUp => app.move_by(-10),
+            Key::Char('/') => {
+                if let Some(filter) = read_line(&mut out, &mut keys, "filter")? {
+                    app.filter = filter;
+                    app.selected = 0;
+                }
+            }
+            Key::Char('s') => {
+                app.state = match app.state {
+                    Some(IssueFilter::Open) => Some(IssueFilter::Closed),
+                    Some(IssueFilter::Closed) => None,
+                    None => Some(IssueFilter::Open),
+                };
+                app.message = "Loading…".to_owned();
+                app.draw(&mut out)?;
+                if let Err(e) = app.reload() {
+                    app.message = format!("Error: {}", e);
+                }
+            }
+            Key::Char('r') => if let Err(e) = app.reload() {
+                app.message = format!("Error: {}", e);
+            },
+            Key::Char('\n') => if let Some(i) = app.current() {
+                let number = app.issues[i].number;
+                match forge::fetch_comments(app.http, app.project, number) {
+                    Ok(comments) => {
+                        app.message = format!("{} comment(s) on #{}", comments.len(), number);
+                        app.comments.insert(number, comments);
+                    }
+                    Err(e) => app.message = format!("Error: {}", e),
+                }
+            },
+            Key::Char('o') => app.on_current(|_, _, issue| {
+                open::that(&issue.url)?;
+                Ok(format!("Opened {}", issue.url))
+            }),
+            Key::Char('c') => app.on_current(|http, project, issue| {
+                let state = if issue.state == "open" {
+                    IssueFilter::Closed
+                } else {
+                    IssueFilter::Open
+                };
+                forge::set_issue_state(http, project, issue.number, &state)?;
+                issue.state = state.to_string();
+                Ok(format!("#{} is now {}", issue.number, issue.state))
+            }),
+            Key::Char('l') => if let Some(labels) = read_line(&mut out, &mut keys, "labels to add (comma separated)")? {
+                let labels: Vec<String> = labels
+                    .split(',')
+                    .map(|l| l.trim().to_owned())
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                if !labels.is_empty() {
+                    app.on_current(|http, project, issue| {
+                        forge::add_labels(http, project, issue.number, &labels)?;
+                        for label in labels {
+                            if !issue.labels.contains(&label) {
+                                issue.labels.push(label);
+                            }
+                        }
+                        Ok(format!("Labelled #{}", issue.number))
+                    });
+                }
+            },
+            Key::Char('a') => if let Some(username) = read_line(&mut out, &mut keys, "assign to")? {
+                let username = username.trim().to_owned();
+                if !username.is_empty() {
+                    app.on_current(|http, project, issue| {
+                        forge::assign_issue(http, project, issue.number, &username)?;
+                        issue.assignees = vec![username];
+                        Ok(format!("Assigned #{}", issue.number))
+                    });
+                }
+            },
+            Key::Char('m') => if let Some(body) = read_line(&mut out, &mut keys, "comment")? {
+                if !body.trim().is_empty() {
+                    app.on_current(|http, project, issue| {
+                        forge::comment_issue(http, project, issue.number, &body)?;
+                        Ok(format!("Commented on #{}", issue.number))
+                    });
+                    // Loaded comments are stale now
+                    if let Some(i) = app.current() {
+                        let number = app.issues[i].number;
+                        app.comments.remove(&number);
+                    }
+                }
+            },
+            Key::Char('?') => app.message.clear(),
+            _ => {}
+        }
+    }
+    write!(out, "{}", cursor::Show)?;
+    out.flush()?;
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod wrap_tests {
+    use super::*;
+
+    #[test]
+    fn wrapping_text() {
+        assert_eq!(wrap("a short line", 20), vec!["a short line"]);
+        assert_eq!(wrap("a short line", 7), vec!["a short", "line"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("first\n\nthird", 10), vec!["first", "", "third"]);
+    }
+}